@@ -1,3 +1,14 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    git::{self, CommandLog, GitBackend, LogEntry},
+    jobs::{Jobs, Message},
+    workspace::Workspace,
+};
+
 pub struct App {
     pub current_screen: CurrentScreen, // the current screen the user is looking at, and will later determine what is rendered.
     pub in_search_bar: bool,
@@ -9,23 +20,95 @@ pub struct App {
     pub selected_branch_command: Option<BranchCommand>,
     pub commands: Scrollable,
     pub branch_commands: Scrollable,
+    pub input: String,
+    pub new_branch: NewBranch,
+    pub confirm: Option<Confirm>,
+    /// The merge, rebase, etc. git stopped in the middle of, refreshed on every key press.
+    pub operation: Option<Operation>,
+    /// What HEAD points at, refreshed on every key press like `operation`.
+    pub head: Head,
+    pub operation_actions: Scrollable,
+    /// Unresolved conflicts while `CurrentScreen::Operation` or `CurrentScreen::Conflicts`.
+    pub conflicts: Vec<Conflict>,
+    /// `conflicts` by path, for navigating `CurrentScreen::Conflicts`.
+    pub conflict_list: Scrollable,
+    /// Output of the last operation step, shown on `CurrentScreen::Operation`.
+    pub operation_output: String,
+    pub merge_modes: Scrollable,
+    pub merge_mode: MergeMode,
+    /// Branches offered for deletion on `CurrentScreen::CleaningUp`.
+    pub cleanup: Branches,
+    /// The part of `cleanup` merged into the base, the others only have a
+    /// gone upstream.
+    pub cleanup_merged: Vec<String>,
+    /// Branches `run_cleanup` couldn't safe delete, asked about one by one.
+    pub force_delete_queue: Vec<String>,
+    pub comparison: Option<Comparison>,
+    /// Scroll offset of the diffstat on `CurrentScreen::Comparing`.
+    pub comparison_scroll: u16,
+    pub worktrees: Vec<Worktree>,
+    /// `worktrees` by path, for navigating `CurrentScreen::ListingWorktrees`.
+    pub worktree_list: Scrollable,
+    /// Git commands running in the background, see `App::poll_jobs`.
+    pub jobs: Jobs,
+    /// Snapshot of `GitBackend::command_log`, newest first, taken when
+    /// `CurrentScreen::CommandLog` opens.
+    pub command_log: Vec<LogEntry>,
+    pub command_log_list: Scrollable,
+    /// Showing the full output of the selected entry instead of the list.
+    pub command_log_expanded: bool,
+    /// Scroll offset of the expanded entry.
+    pub command_log_scroll: u16,
+    /// Root of the repository, or where gitui looked for one when
+    /// `CurrentScreen::NoRepository`.
+    pub path: PathBuf,
+    /// The other repositories, held by whichever `App` is open.
+    pub workspace: Workspace,
+    pub git: Arc<dyn GitBackend>,
 }
 
 impl App {
-    pub fn new() -> App {
+    /// The app for the repository `dir` is in, or the start screen saying
+    /// there is none.
+    pub fn open(dir: PathBuf) -> App {
+        App::open_with_log(dir, Arc::default())
+    }
+
+    /// `open`, recording git invocations into `log` from the start.
+    fn open_with_log(dir: PathBuf, log: Arc<CommandLog>) -> App {
+        match git::discover(&dir, log.clone()) {
+            Some(root) => App::new(git::open(root.clone(), log), root),
+            None => {
+                let mut app = App::new(git::open(dir.clone(), log), dir);
+
+                // started above a bunch of repositories, offer those
+                app.open_workspace();
+                if app.workspace.repos.is_empty() {
+                    app.current_screen = CurrentScreen::NoRepository;
+                }
+
+                app
+            }
+        }
+    }
+
+    pub fn new(git: Arc<dyn GitBackend>, path: PathBuf) -> App {
         App {
             current_screen: CurrentScreen::Main,
             in_search_bar: false,
             search_query: String::from(""),
             error_modal: Modal::Closed,
             errors: Vec::new(),
-            branches: Branches::new(vec![]),
+            branches: Branches::new(git.clone(), vec![]),
             selected_command: None,
             selected_branch_command: None,
             commands: Scrollable::new(
                 vec![
                     (Command::Branch.to_string(), Index(0)),
                     (Command::FetchAll.to_string(), Index(1)),
+                    (Command::Worktrees.to_string(), Index(2)),
+                    (Command::Log.to_string(), Index(3)),
+                    (Command::Workspace.to_string(), Index(4)),
                 ],
                 Some(0),
             ),
@@ -33,11 +116,459 @@ impl App {
                 vec![
                     (BranchCommand::Switch.to_string(), Index(0)),
                     (BranchCommand::Merge.to_string(), Index(1)),
+                    (BranchCommand::Create.to_string(), Index(2)),
+                    (BranchCommand::Delete.to_string(), Index(3)),
+                    (BranchCommand::Rename.to_string(), Index(4)),
+                    (BranchCommand::Rebase.to_string(), Index(5)),
+                    (BranchCommand::CleanUp.to_string(), Index(6)),
+                    (BranchCommand::Compare.to_string(), Index(7)),
+                ],
+                Some(0),
+            ),
+            input: String::from(""),
+            new_branch: NewBranch::default(),
+            confirm: None,
+            operation: None,
+            head: Head::Unknown,
+            operation_actions: Scrollable::new(vec![], Some(0)),
+            conflicts: Vec::new(),
+            conflict_list: Scrollable::new(vec![], Some(0)),
+            operation_output: String::from(""),
+            merge_modes: Scrollable::new(
+                vec![
+                    (MergeMode::Default.to_string(), Index(0)),
+                    (MergeMode::NoFastForward.to_string(), Index(1)),
+                    (MergeMode::Squash.to_string(), Index(2)),
+                    (MergeMode::FastForwardOnly.to_string(), Index(3)),
                 ],
                 Some(0),
             ),
+            merge_mode: MergeMode::Default,
+            cleanup: Branches::new(git.clone(), vec![]),
+            cleanup_merged: Vec::new(),
+            force_delete_queue: Vec::new(),
+            comparison: None,
+            comparison_scroll: 0,
+            worktrees: Vec::new(),
+            worktree_list: Scrollable::new(vec![], Some(0)),
+            jobs: Jobs::new(),
+            command_log: Vec::new(),
+            command_log_list: Scrollable::new(vec![], Some(0)),
+            command_log_expanded: false,
+            command_log_scroll: 0,
+            path,
+            workspace: Workspace::default(),
+            git,
+        }
+    }
+
+    /// Loads `gitui.workspace` along with this repository (or the
+    /// repositories inside `path` when it isn't one) into `workspace`.
+    pub fn open_workspace(&mut self) {
+        let mut roots = vec![self.path.clone()];
+        roots.extend(self.git.workspace_paths().into_iter().map(PathBuf::from));
+
+        self.workspace.load(&roots, &self.path, &self.git);
+        self.current_screen = CurrentScreen::Workspace;
+    }
+
+    pub fn close_workspace(&mut self) {
+        self.current_screen = if git::discover(&self.path, self.git.log()).is_some() {
+            CurrentScreen::Main
+        } else {
+            CurrentScreen::NoRepository
+        };
+    }
+
+    /// Makes the repository at `path` the open one, picking up where it was
+    /// left if it was open before. This app gets parked in the workspace.
+    pub fn switch_repository(&mut self, path: &Path) {
+        if path == self.path {
+            self.current_screen = CurrentScreen::Main;
+            return;
+        }
+
+        let mut workspace = std::mem::take(&mut self.workspace);
+        let next = workspace
+            .unpark(path)
+            .unwrap_or_else(|| App::open(path.to_path_buf()));
+
+        let previous = std::mem::replace(self, next);
+        workspace.park(previous);
+        self.workspace = workspace;
+
+        if let CurrentScreen::Workspace = self.current_screen {
+            self.current_screen = CurrentScreen::Main;
+        }
+        self.branches.reload();
+    }
+
+    /// `git init` where `CurrentScreen::NoRepository` found nothing, then
+    /// starts over in the new repository.
+    pub fn init_repository(&mut self) -> Result<(), GituiError> {
+        self.git.init()?;
+        *self = App::open_with_log(self.path.clone(), self.git.log());
+
+        Ok(())
+    }
+
+    /// Applies the result of a finished background job, returning whether
+    /// one finished so the caller knows the repository may have changed.
+    pub fn poll_jobs(&mut self) -> bool {
+        match self.jobs.finished() {
+            Some(message) => {
+                self.update(message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::FetchedAll(Ok(())) | Message::Merged(Ok(())) | Message::Rebased(Ok(())) => {
+                self.branches.reload()
+            }
+            Message::Merged(Err(GituiError::MergeConflict(_))) => {
+                self.current_screen = CurrentScreen::Conflicts;
+                self.refresh_conflicts();
+            }
+            Message::Rebased(Err(GituiError::RebaseStopped(output))) => self.open_operation(output),
+            Message::FetchedAll(Err(err))
+            | Message::Merged(Err(err))
+            | Message::Rebased(Err(err)) => self.show_error(err),
+        }
+    }
+
+    /// Fails with `GituiError::Busy` while a background job is running, checked
+    /// before every action that changes the repository.
+    pub fn ensure_idle(&self) -> Result<(), GituiError> {
+        self.jobs.ensure_idle()
+    }
+
+    /// Kills the git command the background job is waiting on, the job
+    /// then finishes with `GituiError::Cancelled`.
+    pub fn cancel_job(&self) {
+        if self.jobs.is_running() {
+            self.git.cancel();
+        }
+    }
+
+    pub fn fetch_all(&mut self) -> Result<(), GituiError> {
+        let git = self.git.clone();
+
+        self.jobs.spawn("Fetching all remotes", move || {
+            Message::FetchedAll(git.fetch_all())
+        })
+    }
+
+    /// Merges the selected branch into the checked out one in the
+    /// background. `message` is used for the merge (or squash) commit.
+    pub fn merge_current(
+        &mut self,
+        mode: MergeMode,
+        message: Option<&str>,
+    ) -> Result<(), GituiError> {
+        self.branches
+            .check_protected_checked_out(ProtectedAction::Merge)?;
+        ensure_no_operation(self.git.as_ref())?;
+
+        let Some(mut branch) = self.branches.get_current().cloned() else {
+            return Ok(());
+        };
+        let git = self.git.clone();
+        let message = message.map(str::to_string);
+
+        self.jobs
+            .spawn(&format!("Merging {}", branch.name), move || {
+                Message::Merged(branch.merge(git.as_ref(), mode, message.as_deref()))
+            })
+    }
+
+    /// Backs out of picking a merge mode or editing the merge message.
+    pub fn cancel_merge(&mut self) {
+        self.current_screen = CurrentScreen::ListingBranches;
+        self.input = "".to_string();
+        self.branches.forget_protected();
+    }
+
+    /// Rebases the checked out branch onto the selected one in the background.
+    pub fn rebase_current(&mut self) -> Result<(), GituiError> {
+        self.branches
+            .check_protected_checked_out(ProtectedAction::Rebase)?;
+        ensure_no_operation(self.git.as_ref())?;
+
+        let Some(branch) = self.branches.get_current().cloned() else {
+            return Ok(());
+        };
+        let git = self.git.clone();
+
+        self.jobs
+            .spawn(&format!("Rebasing onto {}", branch.name), move || {
+                Message::Rebased(branch.rebase(git.as_ref()))
+            })
+    }
+
+    pub fn refresh_head(&mut self) {
+        self.head = self.git.head();
+    }
+
+    /// Checks whether git is in the middle of an operation, updating the
+    /// actions offered for it when it changed.
+    pub fn refresh_operation(&mut self) {
+        let operation = self.git.operation();
+
+        if operation != self.operation {
+            self.operation_actions = Scrollable::new(
+                operation
+                    .map(|o| o.actions())
+                    .unwrap_or_default()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, action)| (action.to_string(), Index(i)))
+                    .collect(),
+                Some(0),
+            );
+            self.operation = operation;
         }
     }
+
+    /// Shows `CurrentScreen::Operation` for the operation in progress, if any.
+    pub fn open_operation(&mut self, output: String) {
+        self.refresh_operation();
+
+        if self.operation.is_some() {
+            self.current_screen = CurrentScreen::Operation;
+            self.operation_output = output;
+            self.operation_actions.reset_index();
+            self.refresh_conflicts();
+        }
+    }
+
+    /// Reloads `conflicts` from git, keeping the selection in bounds.
+    pub fn refresh_conflicts(&mut self) {
+        let index = self.conflict_list.get_index();
+
+        self.conflicts = self.git.conflicts();
+        self.conflict_list = Scrollable::new(
+            self.conflicts
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.path.to_string(), Index(i)))
+                .collect(),
+            Some(index.min(self.conflicts.len().saturating_sub(1))),
+        );
+    }
+
+    pub fn get_current_conflict(&self) -> Option<&Conflict> {
+        self.conflict_list
+            .get_current()
+            .and_then(|(_, Index(i))| self.conflicts.get(*i))
+    }
+
+    /// The marked conflicts, or the highlighted one when none are marked.
+    pub fn get_selected_conflicts(&self) -> Vec<Conflict> {
+        let marked = self.conflict_list.get_marked();
+
+        if marked.is_empty() {
+            return self.get_current_conflict().into_iter().cloned().collect();
+        }
+
+        marked
+            .iter()
+            .filter_map(|Index(i)| self.conflicts.get(*i))
+            .cloned()
+            .collect()
+    }
+
+    /// Shows `CurrentScreen::CleaningUp` with every local branch that is
+    /// merged into `base` or whose upstream is gone, all of them marked.
+    /// `run_cleanup` asks before force deleting the unmerged ones.
+    pub fn open_cleanup(&mut self, base: &str) -> Result<(), GituiError> {
+        let merged = self.git.merged_branches(base)?;
+
+        let candidates: Vec<Branch> = self
+            .branches
+            .get_values()
+            .iter()
+            .filter(|b| !b.is_remote() && !b.is_checked_out && b.name != base)
+            .filter(|b| !self.branches.is_protected(&b.name))
+            .filter(|b| merged.contains(&b.name) || b.upstream.as_ref().is_some_and(|u| u.gone))
+            .map(Branch::from)
+            .collect();
+
+        self.cleanup = Branches::new(self.git.clone(), candidates);
+        self.cleanup.mark_all();
+        self.cleanup_merged = merged;
+        self.current_screen = CurrentScreen::CleaningUp;
+
+        Ok(())
+    }
+
+    /// Safe deletes the marked branches of `cleanup`, reporting how each one
+    /// went. The unmerged ones are then offered for force deleting.
+    pub fn run_cleanup(&mut self) {
+        if let Err(err) = self.ensure_idle() {
+            return self.show_error(err);
+        }
+
+        let mut report = Vec::new();
+        let mut unmerged = Vec::new();
+
+        for b in self.cleanup.get_values().iter().filter(|b| b.is_marked) {
+            match b.delete(self.git.as_ref(), false) {
+                Ok(()) => report.push(GituiError::BranchCleanUp(format!("deleted {}", b.name))),
+                Err(GituiError::BranchNotMerged(name)) => unmerged.push(name),
+                Err(err) => report.push(GituiError::BranchCleanUp(format!(
+                    "failed to delete {}: {}",
+                    b.name, err
+                ))),
+            }
+        }
+
+        self.branches.reload();
+        self.current_screen = CurrentScreen::ListingBranches;
+        self.show_report(report);
+
+        self.force_delete_queue = unmerged;
+        self.force_delete_queue.reverse();
+        self.confirm_next_force_delete();
+    }
+
+    /// Asks about the next branch in `force_delete_queue`, going back to
+    /// the report of `run_cleanup` once there are none left.
+    pub fn confirm_next_force_delete(&mut self) {
+        match self.force_delete_queue.pop() {
+            Some(name) => {
+                self.confirm = Some(Confirm::ForceDeleteBranch(name));
+                self.current_screen = CurrentScreen::Confirming;
+            }
+            None => {
+                if matches!(self.error_modal, Modal::Open) {
+                    self.current_screen = CurrentScreen::Errors;
+                }
+            }
+        }
+    }
+
+    /// Reloads `worktrees` from git, keeping the selection in bounds.
+    pub fn refresh_worktrees(&mut self) {
+        let index = self.worktree_list.get_index();
+
+        self.worktrees = self.git.worktrees();
+        self.worktree_list = Scrollable::new(
+            self.worktrees
+                .iter()
+                .enumerate()
+                .map(|(i, w)| (w.path.to_string(), Index(i)))
+                .collect(),
+            Some(index.min(self.worktrees.len().saturating_sub(1))),
+        );
+    }
+
+    pub fn open_command_log(&mut self) {
+        self.command_log = self.git.command_log();
+        self.command_log.reverse();
+        self.command_log_list = Scrollable::new(
+            self.command_log
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| (entry.command(), Index(i)))
+                .collect(),
+            Some(0),
+        );
+        self.command_log_expanded = false;
+        self.command_log_scroll = 0;
+        self.current_screen = CurrentScreen::CommandLog;
+    }
+
+    pub fn get_current_log_entry(&self) -> Option<&LogEntry> {
+        self.command_log_list
+            .get_current()
+            .and_then(|(_, Index(i))| self.command_log.get(*i))
+    }
+
+    pub fn get_current_worktree(&self) -> Option<&Worktree> {
+        self.worktree_list
+            .get_current()
+            .and_then(|(_, Index(i))| self.worktrees.get(*i))
+    }
+
+    /// Compares the two marked branches, or the highlighted branch with the
+    /// checked out one when not exactly two are marked.
+    pub fn open_comparison(&mut self) -> Result<(), GituiError> {
+        let marked: Vec<String> = self
+            .branches
+            .get_marked()
+            .iter()
+            .filter_map(|Index(i)| self.branches.get_values().get(*i))
+            .map(|b| b.get_name())
+            .collect();
+
+        let (left, right) = match &marked[..] {
+            [left, right] => (left.to_string(), right.to_string()),
+            _ => {
+                let Some(left) = self.branches.get_current().map(|b| b.get_name()) else {
+                    return Ok(());
+                };
+                let right = self
+                    .branches
+                    .get_currently_checkedout_name()
+                    .unwrap_or_else(|| "HEAD".to_string());
+                (left, right)
+            }
+        };
+
+        self.comparison = Some(Comparison::new(self.git.as_ref(), &left, &right)?);
+        self.comparison_scroll = 0;
+        self.current_screen = CurrentScreen::Comparing;
+
+        Ok(())
+    }
+
+    /// Safe deletes every marked branch, reporting how each one went.
+    pub fn delete_marked_branches(&mut self) {
+        let marked: Vec<IndexedBranch> = self
+            .branches
+            .get_marked()
+            .iter()
+            .filter_map(|Index(i)| self.branches.get_values().get(*i))
+            .cloned()
+            .collect();
+
+        let report = marked
+            .iter()
+            .map(|b| {
+                let result = self
+                    .branches
+                    .check_protected(&b.name, ProtectedAction::Delete)
+                    .and_then(|()| b.delete(self.git.as_ref(), false));
+
+                GituiError::BranchDelete(match result {
+                    Ok(()) => format!("deleted {}", b.name),
+                    Err(err) => format!("failed to delete {}: {}", b.name, err),
+                })
+            })
+            .collect();
+
+        self.branches.reload();
+        self.show_report(report);
+    }
+
+    /// Shows one line per item of a bulk action in the error modal.
+    pub fn show_report(&mut self, report: Vec<GituiError>) {
+        if !report.is_empty() {
+            self.errors.extend(report);
+            self.error_modal = Modal::Open;
+            self.current_screen = CurrentScreen::Errors;
+        }
+    }
+
+    pub fn show_error(&mut self, err: GituiError) {
+        self.errors.push(err);
+        self.error_modal = Modal::Open;
+        self.current_screen = CurrentScreen::Errors;
+    }
 }
 
 pub enum CurrentScreen {
@@ -45,6 +576,21 @@ pub enum CurrentScreen {
     ListingBranches,
     ListingCommands,
     ListingBranchCommands,
+    CreatingBranch,
+    RenamingBranch,
+    Confirming,
+    Operation,
+    Conflicts,
+    ChoosingMergeMode,
+    EditingMergeMessage,
+    CleaningUp,
+    Comparing,
+    ListingWorktrees,
+    CreatingWorktree,
+    CommandLog,
+    /// Start screen when gitui wasn't started in (or pointed at) a repository.
+    NoRepository,
+    Workspace,
     Errors,
     Exiting,
 }
@@ -52,95 +598,117 @@ pub enum CurrentScreen {
 pub enum Command {
     Branch,
     FetchAll,
+    Worktrees,
+    Log,
+    Workspace,
 }
 
-impl Command {
-    pub fn to_string(&self) -> String {
-        String::from(match self {
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
             Command::Branch => "Branch",
             Command::FetchAll => "Fetch All",
+            Command::Worktrees => "Worktrees",
+            Command::Log => "Command Log",
+            Command::Workspace => "Workspace",
         })
     }
+}
 
+impl Command {
     pub fn next_step(&self, app: &mut App) -> Result<(), GituiError> {
         match self {
             Command::FetchAll => {
-                let stdout = std::process::Command::new("git")
-                    .arg("fetch")
-                    .arg("--all")
-                    .output()
-                    .expect("couldnt fetch")
-                    .stdout;
-
-                let msg = String::from_utf8(stdout).expect("couldn't parse output");
-
-                if !msg.contains("error:") {
-                    app.search_query = "".to_string();
-                    Ok(())
-                } else {
-                    Err(GituiError::FetchAll(format!(
-                        "failed to fetch all. output: {}",
-                        msg
-                    )))
-                }
+                app.fetch_all()?;
+                app.search_query = "".to_string();
+
+                Ok(())
             }
             Command::Branch => {
                 app.current_screen = CurrentScreen::ListingBranchCommands;
                 app.selected_command = Some(Command::Branch);
 
+                Ok(())
+            }
+            Command::Worktrees => {
+                app.current_screen = CurrentScreen::ListingWorktrees;
+                app.selected_command = Some(Command::Worktrees);
+                app.refresh_worktrees();
+
+                Ok(())
+            }
+            Command::Log => {
+                app.selected_command = Some(Command::Log);
+                app.open_command_log();
+
+                Ok(())
+            }
+            Command::Workspace => {
+                app.selected_command = Some(Command::Workspace);
+                app.open_workspace();
+
                 Ok(())
             }
         }
     }
 }
 
-pub fn get_branches() -> Vec<Branch> {
-    let stdout = std::process::Command::new("git")
-        .arg("branch")
-        .output()
-        .expect("to get git branches")
-        .stdout;
+/// `git for-each-ref` format used by `GitCli::branches`, one NUL separated
+/// record per ref, parsed by `parse_branches`.
+pub const BRANCH_FORMAT: &str =
+    "%(HEAD)%00%(refname)%00%(refname:lstrip=2)%00%(symref)%00%(upstream:short)%00%(upstream:track,nobracket)%00%(objectname:short)%00%(committerdate:unix)%00%(authorname)%00%(contents:subject)";
+
+/// Parses the output of `git for-each-ref --format=BRANCH_FORMAT`.
+pub fn parse_branches(output: &str) -> Vec<Branch> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\0').collect();
+            let [head, refname, name, symref, upstream, track, id, date, author, subject] =
+                fields[..]
+            else {
+                return None;
+            };
+
+            // symbolic refs like `origin/HEAD -> origin/main` aren't branches
+            if !symref.is_empty() {
+                return None;
+            }
 
-    String::from_utf8(stdout)
-        .expect("couldnt parse stdout")
-        .split("\n")
-        .into_iter()
-        .filter(|b| b.len() > 0)
-        .map(|b| {
-            let is_checked_out = b.contains("* ");
-            let name = b.replace("* ", "");
-            Branch::new(&name.trim_start(), is_checked_out)
+            let mut branch = if refname.starts_with("refs/remotes/") {
+                let remote = name.split_once('/').map(|(remote, _)| remote).unwrap_or("");
+                Branch::new_remote(name, remote)
+            } else {
+                Branch::new(name, head == "*")
+            };
+            if !upstream.is_empty() {
+                branch.upstream = Some(Upstream::new(upstream, track));
+            }
+            branch.tip = Some(CommitSummary {
+                id: id.to_string(),
+                timestamp: date.parse().unwrap_or(0),
+                author: author.to_string(),
+                subject: subject.to_string(),
+            });
+
+            Some(branch)
         })
         .collect()
 }
 
 impl BranchCommand {
+    /// Every branch command starts on the branch list, the command picks
+    /// what happens to the branch selected there.
     pub fn next_step(&self, app: &mut App) -> Result<(), GituiError> {
-        match self {
-            BranchCommand::Switch => {
-                app.current_screen = CurrentScreen::ListingBranches;
+        app.current_screen = CurrentScreen::ListingBranches;
 
-                app.branches = Branches::new(get_branches());
+        app.branches.reload();
 
-                app.search_query = "".to_string();
-
-                app.selected_branch_command = Some(BranchCommand::Switch);
+        app.search_query = "".to_string();
 
-                Ok(())
-            }
+        app.selected_branch_command = Some(*self);
 
-            BranchCommand::Merge => {
-                app.current_screen = CurrentScreen::ListingBranches;
-
-                app.branches = Branches::new(get_branches());
-
-                app.search_query = "".to_string();
-
-                app.selected_branch_command = Some(BranchCommand::Merge);
-
-                Ok(())
-            }
-        }
+        Ok(())
     }
 }
 
@@ -149,33 +717,602 @@ impl From<&str> for Command {
         match value {
             "Branch" => Command::Branch,
             "Fetch All" => Command::FetchAll,
+            "Worktrees" => Command::Worktrees,
+            "Command Log" => Command::Log,
+            "Workspace" => Command::Workspace,
             _ => panic!("{value} is not a valid command"),
         }
     }
 }
 
-
 impl From<&str> for BranchCommand {
     fn from(value: &str) -> Self {
         match value {
             "Switch" => BranchCommand::Switch,
             "Merge" => BranchCommand::Merge,
+            "Create" => BranchCommand::Create,
+            "Delete" => BranchCommand::Delete,
+            "Rename" => BranchCommand::Rename,
+            "Rebase" => BranchCommand::Rebase,
+            "Clean up branches" => BranchCommand::CleanUp,
+            "Compare" => BranchCommand::Compare,
             _ => panic!("{value} is not a valid command"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum BranchCommand {
     Switch,
     Merge,
+    Create,
+    Delete,
+    Rename,
+    Rebase,
+    CleanUp,
+    Compare,
 }
 
-impl BranchCommand {
-    pub fn to_string(&self) -> String {
-        String::from(match self {
+impl std::fmt::Display for BranchCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
             BranchCommand::Switch => "Switch",
             BranchCommand::Merge => "Merge",
+            BranchCommand::Create => "Create",
+            BranchCommand::Delete => "Delete",
+            BranchCommand::Rename => "Rename",
+            BranchCommand::Rebase => "Rebase",
+            BranchCommand::CleanUp => "Clean up branches",
+            BranchCommand::Compare => "Compare",
+        })
+    }
+}
+
+/// How `IndexedBranch::merge` integrates the branch, picked on
+/// `CurrentScreen::ChoosingMergeMode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeMode {
+    Default,
+    NoFastForward,
+    Squash,
+    FastForwardOnly,
+}
+
+impl MergeMode {
+    pub fn arg(&self) -> Option<&'static str> {
+        match self {
+            MergeMode::Default => None,
+            MergeMode::NoFastForward => Some("--no-ff"),
+            MergeMode::Squash => Some("--squash"),
+            MergeMode::FastForwardOnly => Some("--ff-only"),
+        }
+    }
+
+    /// Whether the merge can create a commit, so there's a message to edit.
+    pub fn has_message(&self) -> bool {
+        !matches!(self, MergeMode::FastForwardOnly)
+    }
+}
+
+impl std::fmt::Display for MergeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MergeMode::Default => "Default",
+            MergeMode::NoFastForward => "No fast-forward (--no-ff)",
+            MergeMode::Squash => "Squash (--squash)",
+            MergeMode::FastForwardOnly => "Fast-forward only (--ff-only)",
+        })
+    }
+}
+
+impl From<&str> for MergeMode {
+    fn from(value: &str) -> Self {
+        match value {
+            "Default" => MergeMode::Default,
+            "No fast-forward (--no-ff)" => MergeMode::NoFastForward,
+            "Squash (--squash)" => MergeMode::Squash,
+            "Fast-forward only (--ff-only)" => MergeMode::FastForwardOnly,
+            _ => panic!("{value} is not a valid merge mode"),
+        }
+    }
+}
+
+/// What HEAD points at. `for-each-ref` only lists branches, so a detached
+/// HEAD never shows up in `Branches` and is tracked here instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Head {
+    /// On a branch, which may not have any commits yet.
+    Branch(String),
+    /// Detached at the commit with this abbreviated id.
+    Detached(String),
+    /// Not in a repository, or HEAD couldn't be read.
+    Unknown,
+}
+
+impl Head {
+    pub fn is_detached(&self) -> bool {
+        matches!(self, Head::Detached(_))
+    }
+}
+
+impl std::fmt::Display for Head {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Head::Branch(name) => write!(f, "{}", name),
+            Head::Detached(id) => write!(f, "HEAD detached at {}", id),
+            Head::Unknown => write!(f, "HEAD unknown"),
+        }
+    }
+}
+
+/// Something git stopped in the middle of, waiting for us to continue or abort.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Merge,
+    /// A `merge --squash` that stopped on conflicts or wasn't committed yet.
+    /// Unlike a merge it leaves no `MERGE_HEAD` behind.
+    SquashMerge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+impl Operation {
+    pub fn actions(&self) -> Vec<OperationAction> {
+        match self {
+            Operation::Merge | Operation::SquashMerge => vec![
+                OperationAction::ResolveConflicts,
+                OperationAction::Continue,
+                OperationAction::Abort,
+            ],
+            Operation::Rebase | Operation::CherryPick | Operation::Revert => vec![
+                OperationAction::Continue,
+                OperationAction::Skip,
+                OperationAction::Abort,
+            ],
+            Operation::Bisect => vec![
+                OperationAction::Good,
+                OperationAction::Bad,
+                OperationAction::Skip,
+                OperationAction::Abort,
+            ],
+        }
+    }
+
+    pub fn command(&self) -> &'static str {
+        match self {
+            Operation::Merge | Operation::SquashMerge => "merge",
+            Operation::Rebase => "rebase",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+            Operation::Bisect => "bisect",
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Operation::Merge => "Merge",
+            Operation::SquashMerge => "Squash merge",
+            Operation::Rebase => "Rebase",
+            Operation::CherryPick => "Cherry-pick",
+            Operation::Revert => "Revert",
+            Operation::Bisect => "Bisect",
+        })
+    }
+}
+
+/// Actions offered on `CurrentScreen::Operation`, see `Operation::actions`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperationAction {
+    Continue,
+    Skip,
+    Abort,
+    ResolveConflicts,
+    Good,
+    Bad,
+}
+
+impl std::fmt::Display for OperationAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OperationAction::Continue => "Continue",
+            OperationAction::Skip => "Skip",
+            OperationAction::Abort => "Abort",
+            OperationAction::ResolveConflicts => "Resolve conflicts",
+            OperationAction::Good => "Good",
+            OperationAction::Bad => "Bad",
+        })
+    }
+}
+
+impl From<&str> for OperationAction {
+    fn from(value: &str) -> Self {
+        match value {
+            "Continue" => OperationAction::Continue,
+            "Skip" => OperationAction::Skip,
+            "Abort" => OperationAction::Abort,
+            "Resolve conflicts" => OperationAction::ResolveConflicts,
+            "Good" => OperationAction::Good,
+            "Bad" => OperationAction::Bad,
+            _ => panic!("{value} is not a valid command"),
+        }
+    }
+}
+
+impl OperationAction {
+    pub fn next_step(&self, app: &mut App) -> Result<(), GituiError> {
+        let Some(operation) = app.operation else {
+            app.current_screen = CurrentScreen::Main;
+            return Ok(());
+        };
+
+        let arg = match (operation, self) {
+            (_, OperationAction::ResolveConflicts) => {
+                app.current_screen = CurrentScreen::Conflicts;
+                app.refresh_conflicts();
+                return Ok(());
+            }
+            (Operation::Bisect, OperationAction::Good) => "good",
+            (Operation::Bisect, OperationAction::Bad) => "bad",
+            (Operation::Bisect, OperationAction::Skip) => "skip",
+            (Operation::Bisect, OperationAction::Abort) => "reset",
+            (_, OperationAction::Continue) => "--continue",
+            (_, OperationAction::Skip) => "--skip",
+            (_, OperationAction::Abort) => "--abort",
+            (_, OperationAction::Good | OperationAction::Bad) => {
+                return Err(GituiError::Operation(format!(
+                    "can't mark a commit {} during a {}",
+                    self.to_string().to_lowercase(),
+                    operation.command()
+                )))
+            }
+        };
+
+        app.ensure_idle()?;
+        let output = app.git.run_operation(operation, arg);
+
+        app.refresh_operation();
+
+        if app.operation.is_some() {
+            // stopped again, stay on the screen with the new state
+            app.refresh_conflicts();
+            app.operation_output = if output.success() {
+                output.stdout
+            } else {
+                output.stderr
+            };
+            return Ok(());
+        }
+
+        app.refresh_conflicts();
+        app.operation_output = "".to_string();
+        app.current_screen = CurrentScreen::ListingBranches;
+        app.branches.reload();
+
+        output
+            .check(
+                GituiError::Operation,
+                &format!(
+                    "{} {}",
+                    self.to_string().to_lowercase(),
+                    operation.command()
+                ),
+            )
+            .map(|_| ())
+    }
+}
+
+/// A working tree attached to the repository, from `git worktree list --porcelain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Worktree {
+    pub path: String,
+    /// Short name of the checked out branch, `None` when detached or bare.
+    pub branch: Option<String>,
+    pub head: String,
+    pub is_bare: bool,
+    pub is_locked: bool,
+    /// Its directory is gone, `git worktree prune` will remove it.
+    pub is_prunable: bool,
+    /// The worktree gitui is running in.
+    pub is_current: bool,
+}
+
+impl Worktree {
+    pub fn get_display_branch(&self) -> String {
+        match &self.branch {
+            Some(branch) => branch.to_string(),
+            None if self.is_bare => "(bare)".to_string(),
+            None => format!("(detached at {})", self.head.get(..7).unwrap_or(&self.head)),
+        }
+    }
+
+    pub fn remove(&self, git: &dyn GitBackend) -> Result<(), GituiError> {
+        if self.is_current {
+            return Err(GituiError::Worktree(
+                "can't remove the worktree gitui is running in".to_string(),
+            ));
+        }
+
+        git.remove_worktree(&self.path)
+    }
+
+    /// Adds a worktree at `path` with `branch` checked out. For remote
+    /// branches a local tracking branch is created, like `IndexedBranch::switch`.
+    pub fn add(git: &dyn GitBackend, path: &str, branch: &IndexedBranch) -> Result<(), GituiError> {
+        if path.is_empty() {
+            return Err(GituiError::Worktree("worktree path is empty".to_string()));
+        }
+
+        if branch.is_remote() {
+            git.add_worktree(path, &branch.name, Some(&branch.local_name()))
+        } else {
+            git.add_worktree(path, &branch.name, None)
+        }
+    }
+
+    /// Default path for a new worktree of `branch`: a sibling of the current
+    /// worktree named `<repo>-<branch>`.
+    pub fn default_path(git: &dyn GitBackend, branch: &IndexedBranch) -> String {
+        let toplevel = git.toplevel().unwrap_or_default();
+        let toplevel = std::path::Path::new(&toplevel);
+        let repo = toplevel
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "worktree".to_string());
+        let dir = format!("{}-{}", repo, branch.local_name().replace('/', "-"));
+
+        match toplevel.parent() {
+            Some(parent) => parent.join(dir).to_string_lossy().to_string(),
+            None => dir,
+        }
+    }
+}
+
+/// Branch names in the order they were last checked out, most recent
+/// first, from the `checkout: moving from A to B` entries of the HEAD
+/// reflog. Keeps the first, most recent, checkout of each entry in
+/// `branches`, which leaves out commits switched to while detached.
+pub fn parse_recent_checkouts(reflog: &str, branches: &[String]) -> Vec<String> {
+    let mut recent: Vec<String> = Vec::new();
+
+    for line in reflog.lines() {
+        let Some(moves) = line.strip_prefix("checkout: moving from ") else {
+            continue;
+        };
+        let Some((_, to)) = moves.rsplit_once(" to ") else {
+            continue;
+        };
+
+        if branches.iter().any(|b| b == to) && !recent.iter().any(|b| b == to) {
+            recent.push(to.to_string());
+        }
+    }
+
+    recent
+}
+
+/// Parses `git worktree list --porcelain`, one blank line separated block
+/// per worktree. `toplevel` marks the current one.
+pub fn parse_worktrees(output: &str, toplevel: Option<&str>) -> Vec<Worktree> {
+    output
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut worktree = Worktree {
+                path: String::new(),
+                branch: None,
+                head: String::new(),
+                is_bare: false,
+                is_locked: false,
+                is_prunable: false,
+                is_current: false,
+            };
+
+            for line in block.lines() {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                match key {
+                    "worktree" => worktree.path = value.to_string(),
+                    "HEAD" => worktree.head = value.to_string(),
+                    "branch" => {
+                        worktree.branch = Some(
+                            value
+                                .strip_prefix("refs/heads/")
+                                .unwrap_or(value)
+                                .to_string(),
+                        )
+                    }
+                    "bare" => worktree.is_bare = true,
+                    "locked" => worktree.is_locked = true,
+                    "prunable" => worktree.is_prunable = true,
+                    _ => {}
+                }
+            }
+
+            if worktree.path.is_empty() {
+                return None;
+            }
+            worktree.is_current = Some(worktree.path.as_str()) == toplevel;
+
+            Some(worktree)
         })
+        .collect()
+}
+
+/// Switching, merging or rebasing while git is in the middle of something
+/// else fails in confusing ways, so refuse up front.
+fn ensure_no_operation(git: &dyn GitBackend) -> Result<(), GituiError> {
+    match git.operation() {
+        Some(operation) => Err(GituiError::OperationInProgress(operation)),
+        None => Ok(()),
+    }
+}
+
+/// How a path conflicts, from the `XY` status of `git status --porcelain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictKind {
+    BothModified,
+    BothAdded,
+    BothDeleted,
+    AddedByUs,
+    AddedByThem,
+    DeletedByUs,
+    DeletedByThem,
+}
+
+impl ConflictKind {
+    fn from_status(xy: &str) -> Option<Self> {
+        match xy {
+            "UU" => Some(ConflictKind::BothModified),
+            "AA" => Some(ConflictKind::BothAdded),
+            "DD" => Some(ConflictKind::BothDeleted),
+            "AU" => Some(ConflictKind::AddedByUs),
+            "UA" => Some(ConflictKind::AddedByThem),
+            "DU" => Some(ConflictKind::DeletedByUs),
+            "UD" => Some(ConflictKind::DeletedByThem),
+            _ => None,
+        }
+    }
+
+    /// Whether our side of the conflict still has the file.
+    pub fn has_ours(&self) -> bool {
+        matches!(
+            self,
+            ConflictKind::BothModified
+                | ConflictKind::BothAdded
+                | ConflictKind::AddedByUs
+                | ConflictKind::DeletedByThem
+        )
+    }
+
+    /// Whether their side of the conflict still has the file.
+    pub fn has_theirs(&self) -> bool {
+        matches!(
+            self,
+            ConflictKind::BothModified
+                | ConflictKind::BothAdded
+                | ConflictKind::AddedByThem
+                | ConflictKind::DeletedByUs
+        )
+    }
+}
+
+impl std::fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConflictKind::BothModified => "both modified",
+            ConflictKind::BothAdded => "both added",
+            ConflictKind::BothDeleted => "both deleted",
+            ConflictKind::AddedByUs => "added by us",
+            ConflictKind::AddedByThem => "added by them",
+            ConflictKind::DeletedByUs => "deleted by us",
+            ConflictKind::DeletedByThem => "deleted by them",
+        })
+    }
+}
+
+/// Which side to keep when resolving a conflict wholesale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    pub kind: ConflictKind,
+}
+
+impl Conflict {
+    pub fn get_display_name(&self) -> String {
+        format!("{:<16} {}", self.kind.to_string(), self.path)
+    }
+
+    /// Resolves the conflict by keeping one side, removing the file when
+    /// that side deleted it, and stages the result.
+    pub fn take(&self, git: &dyn GitBackend, side: Side) -> Result<(), GituiError> {
+        let keeps_file = match side {
+            Side::Ours => self.kind.has_ours(),
+            Side::Theirs => self.kind.has_theirs(),
+        };
+
+        if keeps_file {
+            git.checkout_side(&self.path, side)?;
+            self.mark_resolved(git)
+        } else {
+            git.remove(&self.path)
+        }
+    }
+
+    /// Stages the file as is, telling git the conflict is resolved.
+    pub fn mark_resolved(&self, git: &dyn GitBackend) -> Result<(), GituiError> {
+        if self.kind == ConflictKind::BothDeleted {
+            git.remove(&self.path)
+        } else {
+            git.stage(&self.path)
+        }
+    }
+}
+
+/// Parses the unmerged paths out of `git status --porcelain=v1 -z`.
+pub fn parse_conflicts(stdout: &str) -> Vec<Conflict> {
+    let mut entries = stdout.split('\0').filter(|e| !e.is_empty());
+    let mut conflicts = Vec::new();
+
+    while let Some(entry) = entries.next() {
+        // renames and copies are followed by their original path
+        if entry.starts_with('R') || entry.starts_with('C') {
+            entries.next();
+        }
+
+        if entry.len() < 4 {
+            continue;
+        }
+        let (xy, path) = entry.split_at(2);
+
+        if let Some(kind) = ConflictKind::from_status(xy) {
+            conflicts.push(Conflict {
+                path: path[1..].to_string(),
+                kind,
+            });
+        }
+    }
+
+    conflicts
+}
+
+/// Actions for an in-progress merge on `CurrentScreen::Conflicts`.
+pub enum MergeAction {
+    Commit,
+    Abort,
+}
+
+impl MergeAction {
+    pub fn run(&self, app: &mut App) -> Result<(), GituiError> {
+        app.ensure_idle()?;
+
+        if let MergeAction::Commit = self {
+            app.refresh_conflicts();
+            if !app.conflicts.is_empty() {
+                return Err(GituiError::Conflict(format!(
+                    "{} file(s) still have conflicts, resolve them before committing",
+                    app.conflicts.len()
+                )));
+            }
+        }
+
+        match self {
+            MergeAction::Commit => app.git.commit(None)?,
+            MergeAction::Abort => app.git.abort_merge()?,
+        }
+
+        app.refresh_conflicts();
+        app.current_screen = CurrentScreen::ListingBranches;
+        app.branches.reload();
+
+        Ok(())
     }
 }
 
@@ -184,10 +1321,206 @@ pub enum Modal {
     Closed,
 }
 
+/// An action waiting on an answer in `CurrentScreen::Confirming`.
+pub enum Confirm {
+    /// Force delete a branch that `git branch -d` refused to delete.
+    ForceDeleteBranch(String),
+    /// Go ahead with `action` on a protected branch, confirmed by typing its name.
+    ProtectedBranch {
+        branch: String,
+        action: ProtectedAction,
+    },
+}
+
+impl Confirm {
+    pub fn prompt(&self) -> String {
+        match self {
+            Confirm::ForceDeleteBranch(name) => format!(
+                "Branch '{}' is not fully merged. Force delete it anyway? (y/n)",
+                name
+            ),
+            Confirm::ProtectedBranch { branch, action } => format!(
+                "Branch '{}' is protected. Type its name to {} it anyway",
+                branch, action
+            ),
+        }
+    }
+
+    /// What has to be typed into `App::input` to confirm, `None` for (y/n) confirms.
+    pub fn typed_answer(&self) -> Option<&str> {
+        match self {
+            Confirm::ForceDeleteBranch(_) => None,
+            Confirm::ProtectedBranch { branch, .. } => Some(branch),
+        }
+    }
+}
+
+/// Something gitui refuses to do to a protected branch without confirmation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProtectedAction {
+    Delete,
+    /// Merging into it while it's checked out.
+    Merge,
+    /// Rebasing it while it's checked out, which rewrites its history.
+    Rebase,
+}
+
+impl std::fmt::Display for ProtectedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProtectedAction::Delete => "delete",
+            ProtectedAction::Merge => "merge into",
+            ProtectedAction::Rebase => "rebase",
+        })
+    }
+}
+
+/// Branch name patterns set per repo with
+/// `git config --add gitui.protectedBranch <pattern>`, e.g. `main` or
+/// `release/*`. A `*` matches any run of characters, including `/`.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedBranches {
+    patterns: Vec<String>,
+}
+
+impl ProtectedBranches {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self { patterns }
+    }
+
+    pub fn is_protected(&self, name: &str) -> bool {
+        self.patterns.iter().any(|p| glob_match(p, name))
+    }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
+}
+
+/// Options for the branch being created in `CurrentScreen::CreatingBranch`.
+/// The name itself lives in `App::input`.
+#[derive(Default)]
+pub struct NewBranch {
+    /// Branch to start from, `None` means the current HEAD.
+    pub start_point: Option<String>,
+    pub switch: bool,
+}
+
+impl NewBranch {
+    pub fn new(start_point: Option<String>) -> Self {
+        Self {
+            start_point,
+            switch: true,
+        }
+    }
+}
+
+/// Checks `name` against the rules of `git check-ref-format --branch`,
+/// so we can reject it before running anything.
+pub fn validate_branch_name(name: &str) -> Result<(), GituiError> {
+    let invalid = |reason: &str| {
+        Err(GituiError::InvalidBranchName(format!(
+            "invalid branch name '{}': {}",
+            name, reason
+        )))
+    };
+
+    if name.is_empty() {
+        return invalid("name is empty");
+    }
+    if name == "@" || name == "HEAD" {
+        return invalid("name is reserved");
+    }
+    if name.starts_with('-') {
+        return invalid("cannot start with '-'");
+    }
+    if name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+        return invalid("cannot start or end with '/' or contain '//'");
+    }
+    if name.ends_with('.') {
+        return invalid("cannot end with '.'");
+    }
+    if name.contains("..") {
+        return invalid("cannot contain '..'");
+    }
+    if name.contains("@{") {
+        return invalid("cannot contain '@{'");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_ascii_control() || " ~^:?*[\\".contains(*c))
+    {
+        return invalid(&format!("cannot contain {:?}", c));
+    }
+    for component in name.split('/') {
+        if component.starts_with('.') {
+            return invalid("path components cannot start with '.'");
+        }
+        if component.ends_with(".lock") {
+            return invalid("path components cannot end with '.lock'");
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
 pub enum GituiError {
     BranchSwitch(String),
     FetchAll(String),
     BranchMerge(String),
+    BranchCreate(String),
+    InvalidBranchName(String),
+    BranchRename(String),
+    Compare(String),
+    Worktree(String),
+    /// Refused to run `action` on a branch matching `gitui.protectedBranch`.
+    ProtectedBranch {
+        branch: String,
+        action: ProtectedAction,
+    },
+    /// The branch is checked out in another worktree, so git won't switch to it.
+    BranchInWorktree {
+        branch: String,
+        path: String,
+    },
+    /// One line of the report of `App::run_cleanup`.
+    BranchCleanUp(String),
+    Rebase(String),
+    Operation(String),
+    /// Another operation has to be continued or aborted first.
+    OperationInProgress(Operation),
+    /// The rebase stopped, usually on conflicts, and waits for continue/skip/abort.
+    RebaseStopped(String),
+    /// The merge stopped on conflicts, see `CurrentScreen::Conflicts`.
+    MergeConflict(String),
+    Conflict(String),
+    BranchDelete(String),
+    /// `git branch -d` refused because the branch has unmerged commits.
+    BranchNotMerged(String),
+    /// The checked out branch can't be deleted.
+    BranchDeleteCheckedOut(String),
+    /// A background job, named here, has to finish first.
+    Busy(String),
+    Repository(String),
+    /// The user cancelled this git command line.
+    Cancelled(String),
+    /// Killed after running for `gitui.timeout`.
+    TimedOut {
+        command: String,
+        after: std::time::Duration,
+    },
 }
 
 impl std::fmt::Display for GituiError {
@@ -196,14 +1529,232 @@ impl std::fmt::Display for GituiError {
             GituiError::BranchSwitch(s) => write!(f, "{}", s),
             GituiError::FetchAll(s) => write!(f, "{}", s),
             GituiError::BranchMerge(s) => write!(f, "{}", s),
+            GituiError::BranchCreate(s) => write!(f, "{}", s),
+            GituiError::InvalidBranchName(s) => write!(f, "{}", s),
+            GituiError::BranchRename(s) => write!(f, "{}", s),
+            GituiError::BranchCleanUp(s) => write!(f, "{}", s),
+            GituiError::Compare(s) => write!(f, "{}", s),
+            GituiError::Worktree(s) => write!(f, "{}", s),
+            GituiError::ProtectedBranch { branch, action } => write!(
+                f,
+                "refusing to {} '{}', it is a protected branch",
+                action, branch
+            ),
+            GituiError::BranchInWorktree { branch, path } => write!(
+                f,
+                "can't switch to '{}' because it is checked out in the worktree at {}. \
+                 work there, or remove that worktree first",
+                branch, path
+            ),
+            GituiError::Rebase(s) => write!(f, "{}", s),
+            GituiError::Operation(s) => write!(f, "{}", s),
+            GituiError::OperationInProgress(operation) => write!(
+                f,
+                "a {} is in progress. continue or abort it first (o from the main screen)",
+                operation.command()
+            ),
+            GituiError::RebaseStopped(s) => write!(f, "{}", s),
+            GituiError::MergeConflict(s) => write!(f, "{}", s),
+            GituiError::Busy(job) => write!(f, "wait for '{}' to finish", job),
+            GituiError::Repository(s) => write!(f, "{}", s),
+            GituiError::Cancelled(command) => write!(f, "cancelled {}", command),
+            GituiError::TimedOut { command, after } => {
+                write!(f, "{} timed out after {}s", command, after.as_secs())
+            }
+            GituiError::Conflict(s) => write!(f, "{}", s),
+            GituiError::BranchDelete(s) => write!(f, "{}", s),
+            GituiError::BranchNotMerged(name) => {
+                write!(f, "branch '{}' is not fully merged", name)
+            }
+            GituiError::BranchDeleteCheckedOut(name) => write!(
+                f,
+                "can't delete branch '{}' because it is checked out. switch to another branch first",
+                name
+            ),
         }
     }
 }
 
-#[derive(Debug)]
+/// The upstream a local branch tracks, and how far apart they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upstream {
+    pub name: String,
+    pub ahead: usize,
+    pub behind: usize,
+    /// The upstream ref no longer exists, e.g. after `git fetch --prune`.
+    pub gone: bool,
+}
+
+impl Upstream {
+    /// `track` is `%(upstream:track,nobracket)`, e.g. `ahead 1, behind 2` or `gone`.
+    pub fn new(name: &str, track: &str) -> Self {
+        let mut upstream = Self {
+            name: name.to_string(),
+            ahead: 0,
+            behind: 0,
+            gone: track == "gone",
+        };
+
+        for part in track.split(", ") {
+            match part.split_once(' ') {
+                Some(("ahead", n)) => upstream.ahead = n.parse().unwrap_or(0),
+                Some(("behind", n)) => upstream.behind = n.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+
+        upstream
+    }
+
+    /// Short summary for the branch list, e.g. `↑1 ↓2`, `gone` or `=`.
+    pub fn get_display_track(&self) -> String {
+        if self.gone {
+            return "gone".to_string();
+        }
+
+        match (self.ahead, self.behind) {
+            (0, 0) => "=".to_string(),
+            (ahead, 0) => format!("↑{}", ahead),
+            (0, behind) => format!("↓{}", behind),
+            (ahead, behind) => format!("↑{} ↓{}", ahead, behind),
+        }
+    }
+}
+
+/// The commit a branch points at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitSummary {
+    /// Abbreviated commit hash.
+    pub id: String,
+    /// Committer date as a unix timestamp.
+    pub timestamp: i64,
+    pub author: String,
+    pub subject: String,
+}
+
+/// `git log` format parsed by `parse_commits`, matching the fields of `CommitSummary`.
+pub const LOG_FORMAT: &str = "%h%x00%ct%x00%an%x00%s";
+
+/// Parses the output of `git log --format=LOG_FORMAT`.
+pub fn parse_commits(output: &str) -> Vec<CommitSummary> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let [id, date, author, subject] = line.split('\0').collect::<Vec<_>>()[..] else {
+                return None;
+            };
+
+            Some(CommitSummary {
+                id: id.to_string(),
+                timestamp: date.parse().unwrap_or(0),
+                author: author.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// What two branches have that the other doesn't, shown on `CurrentScreen::Comparing`.
+pub struct Comparison {
+    pub left: String,
+    pub right: String,
+    pub only_left: Vec<CommitSummary>,
+    pub only_right: Vec<CommitSummary>,
+    /// `git diff --stat right...left`, i.e. what merging left into right brings in.
+    pub diffstat: String,
+}
+
+impl Comparison {
+    pub fn new(git: &dyn GitBackend, left: &str, right: &str) -> Result<Self, GituiError> {
+        Ok(Self {
+            left: left.to_string(),
+            right: right.to_string(),
+            only_left: git.commits(&format!("{}..{}", right, left))?,
+            only_right: git.commits(&format!("{}..{}", left, right))?,
+            diffstat: git.diff_stat(right, left)?,
+        })
+    }
+}
+
+impl CommitSummary {
+    /// Committer date relative to now, e.g. `3 days ago`.
+    pub fn get_relative_date(&self) -> String {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        relative_date(now - self.timestamp)
+    }
+}
+
+fn relative_date(seconds: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const WEEK: i64 = 7 * DAY;
+    const MONTH: i64 = 30 * DAY;
+    const YEAR: i64 = 365 * DAY;
+
+    let (n, unit) = match seconds.max(0) {
+        s if s < MINUTE => return "just now".to_string(),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < WEEK => (s / DAY, "day"),
+        s if s < MONTH => (s / WEEK, "week"),
+        s if s < YEAR => (s / MONTH, "month"),
+        s => (s / YEAR, "year"),
+    };
+
+    format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" })
+}
+
+/// Order of the branch list, cycled with (s) in `CurrentScreen::ListingBranches`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BranchSort {
+    /// Local branches first, then by name.
+    #[default]
+    Name,
+    /// Most recently committed to first.
+    Recency,
+    /// Furthest from their upstream first, branches without one last.
+    AheadBehind,
+    /// Most recently switched to first, from the HEAD reflog. Branches never
+    /// checked out follow by name.
+    CheckedOut,
+}
+
+impl BranchSort {
+    pub fn next(&self) -> BranchSort {
+        match self {
+            BranchSort::Name => BranchSort::Recency,
+            BranchSort::Recency => BranchSort::AheadBehind,
+            BranchSort::AheadBehind => BranchSort::CheckedOut,
+            BranchSort::CheckedOut => BranchSort::Name,
+        }
+    }
+}
+
+impl std::fmt::Display for BranchSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BranchSort::Name => "name",
+            BranchSort::Recency => "recency",
+            BranchSort::AheadBehind => "ahead/behind",
+            BranchSort::CheckedOut => "recently checked out",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Branch {
     pub name: String,
     pub is_checked_out: bool,
+    /// The remote this branch lives on, `None` for local branches.
+    /// For remote-tracking branches `name` includes the remote, e.g. `origin/main`.
+    pub remote: Option<String>,
+    pub upstream: Option<Upstream>,
+    pub tip: Option<CommitSummary>,
 }
 
 impl Branch {
@@ -211,89 +1762,203 @@ impl Branch {
         Self {
             name: name.to_string(),
             is_checked_out,
+            remote: None,
+            upstream: None,
+            tip: None,
+        }
+    }
+
+    pub fn new_remote(name: &str, remote: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            is_checked_out: false,
+            remote: Some(remote.to_string()),
+            upstream: None,
+            tip: None,
+        }
+    }
+}
+
+impl From<&IndexedBranch> for Branch {
+    fn from(branch: &IndexedBranch) -> Branch {
+        Branch {
+            name: branch.name.to_string(),
+            is_checked_out: branch.is_checked_out,
+            remote: branch.remote.clone(),
+            upstream: branch.upstream.clone(),
+            tip: branch.tip.clone(),
         }
     }
 }
 
 impl From<&Branch> for Branch {
     fn from(branch: &Branch) -> Branch {
-        Branch::new(branch.name.as_str(), branch.is_checked_out)
+        Branch {
+            name: branch.name.to_string(),
+            is_checked_out: branch.is_checked_out,
+            remote: branch.remote.clone(),
+            upstream: branch.upstream.clone(),
+            tip: branch.tip.clone(),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IndexedBranch {
     pub name: String,
     pub is_checked_out: bool,
+    pub remote: Option<String>,
+    pub upstream: Option<Upstream>,
+    pub tip: Option<CommitSummary>,
     pub index: usize,
+    pub is_marked: bool,
 }
 
 impl IndexedBranch {
-    pub fn new(name: &str, is_checked_out: bool, index: usize) -> Self {
+    pub fn new(branch: &Branch, index: usize) -> Self {
         Self {
-            name: name.to_string(),
-            is_checked_out,
+            name: branch.name.to_string(),
+            is_checked_out: branch.is_checked_out,
+            remote: branch.remote.clone(),
+            upstream: branch.upstream.clone(),
+            tip: branch.tip.clone(),
             index,
+            is_marked: false,
+        }
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Name of the local branch that tracks this remote branch,
+    /// e.g. `feature/x` for `origin/feature/x`.
+    pub fn local_name(&self) -> String {
+        match &self.remote {
+            Some(remote) => self
+                .name
+                .strip_prefix(&format!("{}/", remote))
+                .unwrap_or(&self.name)
+                .to_string(),
+            None => self.get_name(),
         }
     }
 
-    pub fn switch(&mut self) -> Result<(), GituiError> {
+    pub fn switch(&mut self, git: &dyn GitBackend) -> Result<(), GituiError> {
         if self.is_checked_out {
             return Err(GituiError::BranchSwitch(
                 "branch is already checked out".to_string(),
             ));
         }
-        let stdout = std::process::Command::new("git")
-            .arg("switch")
-            .arg(&self.name.trim())
-            .output()
-            .expect("couldnt switch branch")
-            .stdout;
 
-        let msg = String::from_utf8(stdout).expect("couldn't parse output");
+        if !self.is_remote() {
+            if let Some(worktree) = git
+                .worktrees()
+                .into_iter()
+                .find(|w| !w.is_current && w.branch.as_deref() == Some(self.name.as_str()))
+            {
+                return Err(GituiError::BranchInWorktree {
+                    branch: self.get_name(),
+                    path: worktree.path,
+                });
+            }
+        }
 
-        if !msg.contains("error:") {
-            self.is_checked_out = true;
-            Ok(())
+        // switching to `origin/x` directly would detach HEAD, so create a
+        // local branch tracking it instead
+        if self.is_remote() {
+            git.switch_tracking(self.name.trim(), &self.local_name())?;
         } else {
-            Err(GituiError::BranchSwitch(format!(
-                "failed to switch branch. output: {}",
-                msg
-            )))
+            git.switch(self.name.trim())?;
         }
+
+        self.is_checked_out = true;
+        Ok(())
     }
 
-    pub fn merge(&mut self) -> Result<(), GituiError> {
+    /// Merges this branch into the checked out one. `message` is used for
+    /// the merge (or squash) commit, git's default message otherwise.
+    pub fn merge(
+        &mut self,
+        git: &dyn GitBackend,
+        mode: MergeMode,
+        message: Option<&str>,
+    ) -> Result<(), GituiError> {
         if self.is_checked_out {
-            return Err(GituiError::BranchMerge(
-                "cant merge branch".to_string(),
+            return Err(GituiError::BranchMerge("cant merge branch".to_string()));
+        }
+
+        git.merge(self.name.trim(), mode, message)
+    }
+    /// The message git would use for merging this branch into `into`.
+    pub fn default_merge_message(&self, into: Option<&str>) -> String {
+        let message = if self.is_remote() {
+            format!("Merge remote-tracking branch '{}'", self.name)
+        } else {
+            format!("Merge branch '{}'", self.name)
+        };
+
+        match into {
+            Some(into) if into != "main" && into != "master" => {
+                format!("{} into {}", message, into)
+            }
+            _ => message,
+        }
+    }
+
+    /// Rebases the checked out branch onto this one. When git stops midway
+    /// (usually on conflicts) this returns `GituiError::RebaseStopped`.
+    pub fn rebase(&self, git: &dyn GitBackend) -> Result<(), GituiError> {
+        if self.is_checked_out {
+            return Err(GituiError::Rebase(
+                "can't rebase a branch onto itself".to_string(),
             ));
         }
-        let stdout = std::process::Command::new("git")
-            .arg("merge")
-            .arg(&self.name.trim())
-            .output()
-            .expect("couldnt switch branch")
-            .stdout;
 
-        let msg = String::from_utf8(stdout).expect("couldn't parse output");
+        git.rebase(self.name.trim())
+    }
 
-        if !msg.contains("error:") {
-            Ok(())
-        } else {
-            Err(GituiError::BranchSwitch(format!(
-                "failed to merge branch. output: {}",
-                msg
-            )))
+    /// Deletes the branch with `git branch -d`, or `-D` when `force` is set.
+    /// A safe delete of an unmerged branch returns `GituiError::BranchNotMerged`.
+    pub fn delete(&self, git: &dyn GitBackend, force: bool) -> Result<(), GituiError> {
+        if self.is_checked_out {
+            return Err(GituiError::BranchDeleteCheckedOut(self.get_name()));
         }
+        if self.is_remote() {
+            return Err(GituiError::BranchDelete(format!(
+                "can't delete remote branch '{}'",
+                self.name
+            )));
+        }
+
+        git.delete_branch(self.name.trim(), force)
     }
 
+    /// Renames the branch with `git branch -m`, which also moves its
+    /// `branch.<name>.*` config, so the upstream carries over.
+    pub fn rename(&mut self, git: &dyn GitBackend, new_name: &str) -> Result<(), GituiError> {
+        if self.is_remote() {
+            return Err(GituiError::BranchRename(format!(
+                "can't rename remote branch '{}'",
+                self.name
+            )));
+        }
+        validate_branch_name(new_name)?;
+
+        git.rename_branch(self.name.trim(), new_name)?;
+
+        self.name = new_name.to_string();
+
+        Ok(())
+    }
 
     pub fn get_display_name(&self) -> String {
         if self.is_checked_out {
             format!("* {}", self.get_name())
+        } else if let Some(remote) = &self.remote {
+            format!("  [{}] {}", remote, self.local_name())
         } else {
-            self.get_name()
+            format!("  {}", self.get_name())
         }
     }
 
@@ -301,6 +1966,12 @@ impl IndexedBranch {
         self.name.to_string()
     }
 
+    /// Whether the branch is shown for the search `query`, matched against
+    /// the full name so `origin/f` finds remote branches.
+    pub fn matches(&self, query: &str) -> bool {
+        self.name.contains(query)
+    }
+
     pub fn set_is_checked_out(&mut self, value: bool) {
         self.is_checked_out = value;
     }
@@ -308,25 +1979,90 @@ impl IndexedBranch {
 
 impl From<&IndexedBranch> for IndexedBranch {
     fn from(branch: &IndexedBranch) -> IndexedBranch {
-        IndexedBranch::new(branch.name.as_str(), branch.is_checked_out, branch.index)
+        IndexedBranch {
+            name: branch.name.to_string(),
+            is_checked_out: branch.is_checked_out,
+            remote: branch.remote.clone(),
+            upstream: branch.upstream.clone(),
+            tip: branch.tip.clone(),
+            index: branch.index,
+            is_marked: branch.is_marked,
+        }
     }
 }
 
 pub struct Branches {
+    git: Arc<dyn GitBackend>,
     values: Vec<IndexedBranch>,
     curr_index: usize,
+    sort: BranchSort,
+    /// Loaded by `reload`, so lists built with `new` protect nothing.
+    protected: ProtectedBranches,
+    /// A protected branch the user confirmed an action on, used up by the
+    /// next `check_protected`.
+    confirmed_protected: Option<String>,
 }
 
 impl Branches {
-    pub fn new(branches: Vec<Branch>) -> Self {
+    pub fn new(git: Arc<dyn GitBackend>, branches: Vec<Branch>) -> Self {
         let mut indexed: Vec<IndexedBranch> = Vec::new();
         for (i, b) in branches.iter().enumerate() {
-            indexed.push(IndexedBranch::new(&b.name, b.is_checked_out, i));
+            indexed.push(IndexedBranch::new(b, i));
+        }
+
+        let mut branches = Self {
+            git,
+            values: indexed,
+            curr_index: 0,
+            sort: BranchSort::default(),
+            protected: ProtectedBranches::default(),
+            confirmed_protected: None,
+        };
+        branches.apply_sort();
+        branches
+    }
+
+    pub fn get_sort(&self) -> BranchSort {
+        self.sort
+    }
+
+    /// Re-orders the branches, keeping the same branch selected.
+    pub fn set_sort(&mut self, sort: BranchSort) {
+        let selected = self.get_current().map(|b| b.get_name());
+
+        self.sort = sort;
+        self.apply_sort();
+
+        if let Some(i) = selected.and_then(|name| self.values.iter().position(|b| b.name == name)) {
+            self.curr_index = i;
+        }
+    }
+
+    /// Sorts `values` by `sort` and re-assigns each branch's index to its
+    /// new position, so `filtered` and `Scrollable` follow the same order.
+    fn apply_sort(&mut self) {
+        match self.sort {
+            BranchSort::Name => self
+                .values
+                .sort_by(|a, b| (a.is_remote(), &a.name).cmp(&(b.is_remote(), &b.name))),
+            BranchSort::Recency => self.values.sort_by_key(|b| {
+                std::cmp::Reverse(b.tip.as_ref().map(|t| t.timestamp).unwrap_or(0))
+            }),
+            BranchSort::AheadBehind => self.values.sort_by_key(|b| match &b.upstream {
+                Some(u) => (false, std::cmp::Reverse(u.ahead + u.behind)),
+                None => (true, std::cmp::Reverse(0)),
+            }),
+            BranchSort::CheckedOut => {
+                let recent = self.git.recent_checkouts();
+                self.values.sort_by_key(|b| {
+                    let rank = recent.iter().position(|name| *name == b.name);
+                    (rank.is_none(), rank, b.is_remote(), b.name.to_string())
+                })
+            }
         }
 
-        Self {
-            values: indexed,
-            curr_index: 0,
+        for (i, b) in self.values.iter_mut().enumerate() {
+            b.index = i;
         }
     }
 
@@ -345,35 +2081,69 @@ impl Branches {
     pub fn filtered(&self, query: &str) -> Branches {
         let mut branches = Vec::new();
         for b in self.values.iter() {
-            if query != "" {
-                if b.name.contains(query) {
-                    branches.push(IndexedBranch::from(b));
-                }
-            } else {
+            if b.matches(query) {
                 branches.push(IndexedBranch::from(b));
             }
         }
         Branches {
+            git: self.git.clone(),
             values: branches,
             curr_index: self.get_index(),
+            sort: self.sort,
+            protected: self.protected.clone(),
+            confirmed_protected: self.confirmed_protected.clone(),
         }
     }
 
+    pub fn get_current(&self) -> Option<&IndexedBranch> {
+        self.values.get(self.curr_index)
+    }
+
     pub fn select_from_index(&mut self, index: usize) -> &IndexedBranch {
         self.curr_index = index;
         &self.values[self.curr_index]
     }
 
     pub fn get_currently_checkedout_name(&self) -> Option<String> {
-        if let Some(b) = self.values.iter().find(|b| b.is_checked_out) {
-            Some(b.name.to_string())
-        } else {
-            None
-        }
+        self.values
+            .iter()
+            .find(|b| b.is_checked_out)
+            .map(|b| b.name.to_string())
     }
 
     pub fn switch_current(&mut self) -> Result<(), GituiError> {
-        self.values[self.curr_index].switch()?;
+        ensure_no_operation(self.git.as_ref())?;
+
+        let Some(current) = self.get_current() else {
+            return Ok(());
+        };
+
+        if current.is_remote() {
+            let local_name = current.local_name();
+
+            // a local branch for this remote one already exists, use that
+            // instead of failing to create it again
+            if let Some(local) = self
+                .values
+                .iter()
+                .position(|b| !b.is_remote() && b.name == local_name)
+            {
+                self.curr_index = local;
+                return self.switch_current();
+            }
+
+            self.values[self.curr_index].switch(self.git.as_ref())?;
+
+            // a new local branch was created, so reload and select it
+            self.reload();
+            if let Some(i) = self.values.iter().position(|b| b.is_checked_out) {
+                self.curr_index = i;
+            }
+
+            return Ok(());
+        }
+
+        self.values[self.curr_index].switch(self.git.as_ref())?;
 
         let current_branch_name = &self.values[self.curr_index].name;
 
@@ -382,12 +2152,205 @@ impl Branches {
         Ok(())
     }
 
-    pub fn merge_current(&mut self) -> Result<(), GituiError> {
-        self.values[self.curr_index].merge()?;
+    /// Switches back to the branch checked out before the current one, like
+    /// `git switch -`.
+    pub fn switch_previous(&mut self) -> Result<(), GituiError> {
+        let previous = self.git.previous_branch().ok_or_else(|| {
+            GituiError::BranchSwitch("no previously checked out branch".to_string())
+        })?;
+
+        let index = self
+            .values
+            .iter()
+            .position(|b| !b.is_remote() && b.name == previous)
+            .ok_or_else(|| {
+                GituiError::BranchSwitch(format!(
+                    "previously checked out branch '{}' no longer exists",
+                    previous
+                ))
+            })?;
+
+        self.curr_index = index;
+        self.switch_current()
+    }
+
+    pub fn is_protected(&self, name: &str) -> bool {
+        self.protected.is_protected(name)
+    }
+
+    /// Fails with `GituiError::ProtectedBranch` if `name` is protected and
+    /// the user hasn't confirmed the action through `confirm_protected`.
+    /// A confirmation lets a single check through, whatever it was for.
+    pub fn check_protected(
+        &mut self,
+        name: &str,
+        action: ProtectedAction,
+    ) -> Result<(), GituiError> {
+        let result = self.peek_protected(name, action);
+        self.confirmed_protected = None;
+
+        result
+    }
+
+    /// `check_protected` without using up the confirmation.
+    fn peek_protected(&self, name: &str, action: ProtectedAction) -> Result<(), GituiError> {
+        if self.is_protected(name) && self.confirmed_protected.as_deref() != Some(name) {
+            return Err(GituiError::ProtectedBranch {
+                branch: name.to_string(),
+                action,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// `check_protected` for the checked out branch, which merges and
+    /// rebases rewrite.
+    pub fn check_protected_checked_out(
+        &mut self,
+        action: ProtectedAction,
+    ) -> Result<(), GituiError> {
+        match self.get_currently_checkedout_name() {
+            Some(name) => self.check_protected(&name, action),
+            None => Ok(()),
+        }
+    }
+
+    /// `check_protected_checked_out` leaving the confirmation for the action
+    /// itself, for asking before the user picks how to run it.
+    pub fn peek_protected_checked_out(&self, action: ProtectedAction) -> Result<(), GituiError> {
+        match self.get_currently_checkedout_name() {
+            Some(name) => self.peek_protected(&name, action),
+            None => Ok(()),
+        }
+    }
+
+    /// Lets the next action on `name` through `check_protected`.
+    pub fn confirm_protected(&mut self, name: &str) {
+        self.confirmed_protected = Some(name.to_string());
+    }
+
+    /// Drops a confirmation the user backed out of using.
+    pub fn forget_protected(&mut self) {
+        self.confirmed_protected = None;
+    }
+
+    pub fn delete_current(&mut self, force: bool) -> Result<(), GituiError> {
+        let Some(current) = self.get_current().cloned() else {
+            return Ok(());
+        };
+        self.check_protected(&current.name, ProtectedAction::Delete)?;
+
+        match current.delete(self.git.as_ref(), force) {
+            // still confirmed for the force delete asked for next
+            Err(GituiError::BranchNotMerged(name)) => {
+                if self.is_protected(&name) {
+                    self.confirm_protected(&name);
+                }
+                return Err(GituiError::BranchNotMerged(name));
+            }
+            result => result?,
+        }
+
+        self.reload();
+
+        Ok(())
+    }
+
+    pub fn rename_current(&mut self, new_name: &str) -> Result<(), GituiError> {
+        let Some(current) = self.values.get_mut(self.curr_index) else {
+            return Ok(());
+        };
+
+        if current.name == new_name {
+            return Ok(());
+        }
+
+        current.rename(self.git.as_ref(), new_name)
+    }
+
+    /// Force deletes the branch called `name`, used once the user confirmed it.
+    pub fn force_delete(&mut self, name: &str) -> Result<(), GituiError> {
+        self.check_protected(name, ProtectedAction::Delete)?;
+
+        if let Some(b) = self
+            .values
+            .iter()
+            .find(|b| !b.is_remote() && b.name == name)
+        {
+            b.delete(self.git.as_ref(), true)?;
+        }
+
+        self.reload();
+
+        Ok(())
+    }
+
+    /// Reloads the branches from git, keeping the sort and the selection in bounds.
+    pub fn reload(&mut self) {
+        let index = self.curr_index;
+        let sort = self.sort;
+
+        *self = Branches::new(self.git.clone(), self.git.branches());
+        self.sort = sort;
+        self.protected = ProtectedBranches::new(self.git.protected_patterns());
+        self.apply_sort();
+        self.curr_index = index.min(self.values.len().saturating_sub(1));
+    }
+
+    /// Creates `name` from `new_branch.start_point` (HEAD if unset), then
+    /// reloads the list and selects the new branch.
+    pub fn create(&mut self, name: &str, new_branch: &NewBranch) -> Result<(), GituiError> {
+        validate_branch_name(name)?;
+
+        self.git
+            .create_branch(name, new_branch.start_point.as_deref(), new_branch.switch)?;
+
+        self.reload();
+        if let Some(i) = self
+            .values
+            .iter()
+            .position(|b| !b.is_remote() && b.name == name)
+        {
+            self.curr_index = i;
+        }
 
         Ok(())
     }
 
+    pub fn toggle_mark_current(&mut self) {
+        if let Some(b) = self.values.get_mut(self.curr_index) {
+            b.is_marked = !b.is_marked;
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        for b in self.values.iter_mut() {
+            b.is_marked = true;
+        }
+    }
+
+    pub fn invert_marks(&mut self) {
+        for b in self.values.iter_mut() {
+            b.is_marked = !b.is_marked;
+        }
+    }
+
+    pub fn clear_marks(&mut self) {
+        for b in self.values.iter_mut() {
+            b.is_marked = false;
+        }
+    }
+
+    /// Indexes of the marked branches, for actions that run on all of them.
+    pub fn get_marked(&self) -> Vec<Index> {
+        self.values
+            .iter()
+            .filter(|b| b.is_marked)
+            .map(|b| Index(b.index))
+            .collect()
+    }
+
     pub fn uncheckout_all_except(&mut self, name: String) {
         for b in self.values.iter_mut() {
             if name != b.name {
@@ -399,22 +2362,27 @@ impl Branches {
 
 impl From<&Branches> for Scrollable {
     fn from(branches: &Branches) -> Scrollable {
-        Scrollable::new(
+        let mut scrollable = Scrollable::new(
             branches
                 .values
                 .iter()
-                .map(|b| (String::from(b.get_display_name()), Index(b.index)))
+                .map(|b| (b.get_display_name(), Index(b.index)))
                 .collect(),
             Some(branches.curr_index),
-        )
+        );
+        scrollable.marked = branches.get_marked().into_iter().collect();
+        scrollable
     }
 }
 
 pub struct Scrollable {
     items: Vec<(String, Index)>,
     selection: usize,
+    /// Marked items, by their `Index` so marks survive filtering.
+    marked: std::collections::BTreeSet<Index>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Index(pub usize);
 
 impl Scrollable {
@@ -422,13 +2390,49 @@ impl Scrollable {
         Self {
             items,
             selection: index.unwrap_or(0),
+            marked: std::collections::BTreeSet::new(),
+        }
+    }
+
+    pub fn toggle_mark_current(&mut self) {
+        if let Some((_, index)) = self.get_current() {
+            let index = *index;
+            if !self.marked.remove(&index) {
+                self.marked.insert(index);
+            }
         }
     }
 
+    pub fn mark_all(&mut self) {
+        self.marked = self.items.iter().map(|(_, index)| *index).collect();
+    }
+
+    pub fn invert_marks(&mut self) {
+        self.marked = self
+            .items
+            .iter()
+            .map(|(_, index)| *index)
+            .filter(|index| !self.marked.contains(index))
+            .collect();
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    pub fn is_marked(&self, index: &Index) -> bool {
+        self.marked.contains(index)
+    }
+
+    /// Indexes of the marked items, for actions that run on all of them.
+    pub fn get_marked(&self) -> Vec<Index> {
+        self.marked.iter().copied().collect()
+    }
+
     pub fn filtered(&self, query: &str) -> Scrollable {
         let mut scrollable = Vec::new();
         for item in self.items.iter() {
-            if query != "" {
+            if !query.is_empty() {
                 if item.0.contains(query) {
                     scrollable.push((String::from(&item.0), Index(item.1 .0)));
                 }
@@ -436,7 +2440,9 @@ impl Scrollable {
                 scrollable.push((String::from(&item.0), Index(item.1 .0)));
             }
         }
-        Scrollable::new(scrollable, Some(self.selection))
+        let mut filtered = Scrollable::new(scrollable, Some(self.selection));
+        filtered.marked = self.marked.clone();
+        filtered
     }
 
     pub fn get_current(&self) -> Option<&(String, Index)> {
@@ -511,3 +2517,821 @@ impl Scrollable {
         &self.items
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::git::fake::{FakeGit, FakeRepo};
+
+    fn repo(local: &[&str], checked_out: &str) -> FakeRepo {
+        FakeRepo {
+            branches: local
+                .iter()
+                .map(|name| Branch::new(name, *name == checked_out))
+                .collect(),
+            ..FakeRepo::default()
+        }
+    }
+
+    fn app(repo: FakeRepo) -> (App, Arc<FakeGit>) {
+        let git = Arc::new(FakeGit::new(repo));
+        let mut app = App::new(git.clone(), PathBuf::from("/repo"));
+        app.branches.reload();
+        (app, git)
+    }
+
+    fn select(app: &mut App, name: &str) {
+        let i = app
+            .branches
+            .get_values()
+            .iter()
+            .position(|b| b.name == name)
+            .unwrap();
+        app.branches.select_from_index(i);
+    }
+
+    fn calls(git: &FakeGit) -> Vec<String> {
+        git.repo.lock().unwrap().calls.clone()
+    }
+
+    /// Waits for the running background job and applies its result.
+    fn finish_job(app: &mut App) {
+        for _ in 0..1000 {
+            if app.poll_jobs() {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("background job didn't finish");
+    }
+
+    #[test]
+    fn parses_branches_and_skips_symrefs() {
+        let output = [
+            [
+                "*",
+                "refs/heads/main",
+                "main",
+                "",
+                "origin/main",
+                "ahead 1, behind 2",
+                "abc1234",
+                "100",
+                "Ann",
+                "init",
+            ],
+            [
+                " ",
+                "refs/remotes/origin/HEAD",
+                "origin/HEAD",
+                "refs/remotes/origin/main",
+                "",
+                "",
+                "abc1234",
+                "100",
+                "Ann",
+                "init",
+            ],
+            [
+                " ",
+                "refs/remotes/origin/main",
+                "origin/main",
+                "",
+                "",
+                "",
+                "def5678",
+                "200",
+                "Bob",
+                "fix",
+            ],
+        ]
+        .map(|fields| fields.join("\0"))
+        .join("\n");
+
+        let branches = parse_branches(&output);
+
+        assert_eq!(branches.len(), 2);
+        assert!(branches[0].is_checked_out);
+        let upstream = branches[0].upstream.as_ref().unwrap();
+        assert_eq!((upstream.ahead, upstream.behind), (1, 2));
+        assert_eq!(branches[1].remote.as_deref(), Some("origin"));
+        assert_eq!(branches[1].tip.as_ref().unwrap().author, "Bob");
+    }
+
+    #[test]
+    fn parses_recent_checkouts_most_recent_first() {
+        let reflog = "checkout: moving from main to feature\n\
+                      commit: wip\n\
+                      checkout: moving from abc1234 to main\n\
+                      checkout: moving from feature to abc1234\n\
+                      checkout: moving from main to feature\n";
+        let branches = vec!["main".to_string(), "feature".to_string()];
+
+        assert_eq!(
+            parse_recent_checkouts(reflog, &branches),
+            vec!["feature", "main"]
+        );
+    }
+
+    #[test]
+    fn parses_conflicts_after_a_rename_with_a_short_source() {
+        let conflicts = parse_conflicts("R  new.rs\0old\0UU conflict.txt\0");
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "conflict.txt");
+    }
+
+    #[test]
+    fn parses_main_detached_bare_locked_and_prunable_worktrees() {
+        let output = "worktree /srv/repo.git\n\
+                      bare\n\
+                      \n\
+                      worktree /src/repo\n\
+                      HEAD 1111111111111111111111111111111111111111\n\
+                      branch refs/heads/main\n\
+                      \n\
+                      worktree /src/repo-detached\n\
+                      HEAD 2222222222222222222222222222222222222222\n\
+                      detached\n\
+                      \n\
+                      worktree /mnt/usb/repo-locked\n\
+                      HEAD 3333333333333333333333333333333333333333\n\
+                      branch refs/heads/feature/usb\n\
+                      locked on a usb stick\n\
+                      \n\
+                      worktree /tmp/repo-gone\n\
+                      HEAD 4444444444444444444444444444444444444444\n\
+                      branch refs/heads/old\n\
+                      prunable gitdir file points to non-existent location\n\
+                      \n";
+        let worktree = |path: &str, branch: Option<&str>, head: &str| Worktree {
+            path: path.to_string(),
+            branch: branch.map(str::to_string),
+            head: head.repeat(40),
+            is_bare: false,
+            is_locked: false,
+            is_prunable: false,
+            is_current: false,
+        };
+
+        assert_eq!(
+            parse_worktrees(output, Some("/src/repo")),
+            vec![
+                Worktree {
+                    is_bare: true,
+                    ..worktree("/srv/repo.git", None, "")
+                },
+                Worktree {
+                    is_current: true,
+                    ..worktree("/src/repo", Some("main"), "1")
+                },
+                worktree("/src/repo-detached", None, "2"),
+                Worktree {
+                    is_locked: true,
+                    ..worktree("/mnt/usb/repo-locked", Some("feature/usb"), "3")
+                },
+                Worktree {
+                    is_prunable: true,
+                    ..worktree("/tmp/repo-gone", Some("old"), "4")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn protected_patterns_match_globs() {
+        let protected = ProtectedBranches::new(vec!["main".to_string(), "release/*".to_string()]);
+
+        assert!(protected.is_protected("main"));
+        assert!(protected.is_protected("release/1.0"));
+        assert!(protected.is_protected("release/"));
+        assert!(!protected.is_protected("mainline"));
+        assert!(!protected.is_protected("feature/release/1.0"));
+    }
+
+    #[test]
+    fn validates_branch_names_like_git_check_ref_format() {
+        let cases = [
+            ("feat/x", None),
+            ("", Some("name is empty")),
+            ("-x", Some("cannot start with '-'")),
+            ("a..b", Some("cannot contain '..'")),
+            ("a@{1}", Some("cannot contain '@{'")),
+            ("a.lock", Some("cannot end with '.lock'")),
+            ("feat/a.lock/b", Some("cannot end with '.lock'")),
+            ("feat/", Some("cannot start or end with '/'")),
+            ("feat.", Some("cannot end with '.'")),
+            ("a b", Some("cannot contain ' '")),
+            ("a\tb", Some("cannot contain '\\t'")),
+            ("a\u{7f}b", Some("cannot contain '\\u{7f}'")),
+        ];
+
+        for (name, reason) in cases {
+            match (validate_branch_name(name), reason) {
+                (Ok(()), None) => {}
+                (Err(GituiError::InvalidBranchName(message)), Some(reason)) => {
+                    assert!(message.contains(reason), "{:?}: {}", name, message)
+                }
+                (result, _) => panic!("{:?}: expected {:?}, got {:?}", name, reason, result),
+            }
+        }
+    }
+
+    #[test]
+    fn relative_dates_switch_units_at_each_boundary() {
+        const DAY: i64 = 24 * 60 * 60;
+        let cases = [
+            (-5, "just now"),
+            (59, "just now"),
+            (60, "1 minute ago"),
+            (60 * 60 - 1, "59 minutes ago"),
+            (60 * 60, "1 hour ago"),
+            (DAY - 1, "23 hours ago"),
+            (DAY, "1 day ago"),
+            (7 * DAY - 1, "6 days ago"),
+            (7 * DAY, "1 week ago"),
+            (30 * DAY - 1, "4 weeks ago"),
+            (30 * DAY, "1 month ago"),
+            (365 * DAY - 1, "12 months ago"),
+            (365 * DAY, "1 year ago"),
+            (3 * 365 * DAY, "3 years ago"),
+        ];
+
+        for (seconds, expected) in cases {
+            assert_eq!(relative_date(seconds), expected, "{}s", seconds);
+        }
+    }
+
+    fn sorted(app: &mut App, sort: BranchSort) -> Vec<String> {
+        app.branches.set_sort(sort);
+        app.branches
+            .get_values()
+            .iter()
+            .map(|b| b.name.to_string())
+            .collect()
+    }
+
+    fn with_tip(name: &str, timestamp: i64) -> Branch {
+        Branch {
+            tip: Some(CommitSummary {
+                id: "abc1234".to_string(),
+                timestamp,
+                author: "someone".to_string(),
+                subject: "work".to_string(),
+            }),
+            ..Branch::new(name, false)
+        }
+    }
+
+    fn with_upstream(name: &str, track: &str) -> Branch {
+        Branch {
+            upstream: Some(Upstream::new(&format!("origin/{}", name), track)),
+            ..Branch::new(name, false)
+        }
+    }
+
+    #[test]
+    fn sort_keys_cycle_back_to_name() {
+        let mut sort = BranchSort::default();
+        let mut seen = vec![sort];
+        for _ in 0..4 {
+            sort = sort.next();
+            seen.push(sort);
+        }
+
+        assert_eq!(
+            seen,
+            vec![
+                BranchSort::Name,
+                BranchSort::Recency,
+                BranchSort::AheadBehind,
+                BranchSort::CheckedOut,
+                BranchSort::Name
+            ]
+        );
+    }
+
+    #[test]
+    fn name_sort_puts_local_branches_first() {
+        let (mut app, _git) = app(FakeRepo {
+            branches: vec![
+                Branch::new_remote("origin/a", "origin"),
+                Branch::new("b", true),
+                Branch::new("a", false),
+            ],
+            ..FakeRepo::default()
+        });
+
+        assert_eq!(sorted(&mut app, BranchSort::Name), ["a", "b", "origin/a"]);
+    }
+
+    #[test]
+    fn recency_sort_puts_newest_tips_first_and_ties_by_name() {
+        let (mut app, _git) = app(FakeRepo {
+            branches: vec![
+                Branch::new("no-tip", false),
+                with_tip("old", 100),
+                with_tip("new-b", 300),
+                with_tip("new-a", 300),
+            ],
+            ..FakeRepo::default()
+        });
+
+        assert_eq!(
+            sorted(&mut app, BranchSort::Recency),
+            ["new-a", "new-b", "old", "no-tip"]
+        );
+    }
+
+    #[test]
+    fn ahead_behind_sort_puts_furthest_first_and_no_upstream_last() {
+        let (mut app, _git) = app(FakeRepo {
+            branches: vec![
+                Branch::new("local", false),
+                with_upstream("even", ""),
+                with_upstream("both", "ahead 1, behind 1"),
+                with_upstream("ahead", "ahead 3"),
+                with_upstream("behind", "behind 2"),
+            ],
+            ..FakeRepo::default()
+        });
+
+        assert_eq!(
+            sorted(&mut app, BranchSort::AheadBehind),
+            ["ahead", "behind", "both", "even", "local"]
+        );
+    }
+
+    #[test]
+    fn checked_out_sort_follows_the_reflog_then_names() {
+        let (mut app, _git) = app(FakeRepo {
+            branches: vec![
+                Branch::new_remote("origin/x", "origin"),
+                Branch::new("never-b", false),
+                Branch::new("never-a", false),
+                Branch::new("first", false),
+                Branch::new("second", false),
+                Branch::new("last", true),
+            ],
+            checkouts: ["first", "last", "second", "last"]
+                .map(String::from)
+                .to_vec(),
+            ..FakeRepo::default()
+        });
+
+        assert_eq!(
+            sorted(&mut app, BranchSort::CheckedOut),
+            ["last", "second", "first", "never-a", "never-b", "origin/x"]
+        );
+    }
+
+    #[test]
+    fn changing_the_sort_keeps_the_selection() {
+        let (mut app, _git) = app(FakeRepo {
+            branches: vec![with_tip("a", 100), with_tip("b", 300), with_tip("c", 200)],
+            ..FakeRepo::default()
+        });
+        select(&mut app, "a");
+
+        sorted(&mut app, BranchSort::Recency);
+
+        assert_eq!(app.branches.get_current().unwrap().name, "a");
+        assert_eq!(app.branches.get_index(), 2);
+    }
+
+    #[test]
+    fn switch_current_checks_out_the_selected_branch() {
+        let (mut app, git) = app(repo(&["feature", "main"], "main"));
+        select(&mut app, "feature");
+
+        app.branches.switch_current().unwrap();
+
+        assert_eq!(
+            app.branches.get_currently_checkedout_name().as_deref(),
+            Some("feature")
+        );
+        assert_eq!(calls(&git), vec!["switch feature"]);
+    }
+
+    #[test]
+    fn search_matches_remote_branches_by_full_name() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.branches
+            .push(Branch::new_remote("origin/feature", "origin"));
+        let (app, _) = app(repo);
+
+        let names: Vec<String> = app
+            .branches
+            .filtered("origin/f")
+            .get_values()
+            .iter()
+            .map(|b| b.get_name())
+            .collect();
+
+        assert_eq!(names, vec!["origin/feature"]);
+    }
+
+    #[test]
+    fn switch_current_to_remote_uses_an_existing_local_branch() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.branches
+            .push(Branch::new_remote("origin/feature", "origin"));
+        let (mut app, git) = app(repo);
+        select(&mut app, "origin/feature");
+
+        app.branches.switch_current().unwrap();
+
+        assert_eq!(calls(&git), vec!["switch feature"]);
+    }
+
+    #[test]
+    fn switch_current_to_remote_creates_a_tracking_branch() {
+        let mut repo = repo(&["main"], "main");
+        repo.branches
+            .push(Branch::new_remote("origin/feature", "origin"));
+        let (mut app, git) = app(repo);
+        select(&mut app, "origin/feature");
+
+        app.branches.switch_current().unwrap();
+
+        assert_eq!(
+            calls(&git),
+            vec!["switch --create feature --track origin/feature"]
+        );
+        let current = app.branches.get_current().unwrap();
+        assert_eq!(current.name, "feature");
+        assert!(current.is_checked_out);
+    }
+
+    #[test]
+    fn switch_is_refused_during_an_operation() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.operation = Some(Operation::Rebase);
+        let (mut app, git) = app(repo);
+        select(&mut app, "feature");
+
+        let result = app.branches.switch_current();
+
+        assert!(matches!(
+            result,
+            Err(GituiError::OperationInProgress(Operation::Rebase))
+        ));
+        assert!(calls(&git).is_empty());
+    }
+
+    #[test]
+    fn switch_is_refused_for_a_branch_in_another_worktree() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.worktrees.push(Worktree {
+            path: "/elsewhere".to_string(),
+            branch: Some("feature".to_string()),
+            head: String::new(),
+            is_bare: false,
+            is_locked: false,
+            is_prunable: false,
+            is_current: false,
+        });
+        let (mut app, _) = app(repo);
+        select(&mut app, "feature");
+
+        let result = app.branches.switch_current();
+
+        assert!(matches!(
+            result,
+            Err(GituiError::BranchInWorktree { path, .. }) if path == "/elsewhere"
+        ));
+    }
+
+    #[test]
+    fn switch_previous_goes_back_to_the_last_branch() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.checkouts = vec!["feature".to_string(), "main".to_string()];
+        let (mut app, _) = app(repo);
+
+        app.branches.switch_previous().unwrap();
+
+        assert_eq!(
+            app.branches.get_currently_checkedout_name().as_deref(),
+            Some("feature")
+        );
+    }
+
+    #[test]
+    fn deleting_an_unmerged_branch_needs_force() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.unmerged = vec!["feature".to_string()];
+        let (mut app, git) = app(repo);
+        select(&mut app, "feature");
+
+        let result = app.branches.delete_current(false);
+        assert!(matches!(result, Err(GituiError::BranchNotMerged(name)) if name == "feature"));
+
+        app.branches.force_delete("feature").unwrap();
+
+        assert_eq!(calls(&git), vec!["branch -d feature", "branch -D feature"]);
+        assert_eq!(app.branches.get_values().len(), 1);
+    }
+
+    #[test]
+    fn switching_on_an_empty_list_does_nothing() {
+        let (mut app, git) = app(repo(&[], "main"));
+
+        app.branches.switch_current().unwrap();
+
+        assert!(calls(&git).is_empty());
+    }
+
+    #[test]
+    fn deleting_from_an_empty_list_does_nothing() {
+        let (mut app, git) = app(repo(&[], "main"));
+
+        app.branches.delete_current(false).unwrap();
+
+        assert!(calls(&git).is_empty());
+    }
+
+    #[test]
+    fn cleanup_asks_before_force_deleting_branches_with_a_gone_upstream() {
+        let mut repo = repo(&["main", "merged", "squashed"], "main");
+        repo.branches[2].upstream = Some(Upstream::new("origin/squashed", "gone"));
+        repo.unmerged = vec!["squashed".to_string()];
+        let (mut app, git) = app(repo);
+
+        app.open_cleanup("main").unwrap();
+        assert!(app.cleanup.get_values().iter().all(|b| b.is_marked));
+
+        app.run_cleanup();
+
+        assert_eq!(calls(&git), vec!["branch -d merged", "branch -d squashed"]);
+        assert!(matches!(
+            &app.confirm,
+            Some(Confirm::ForceDeleteBranch(name)) if name == "squashed"
+        ));
+        assert!(matches!(app.current_screen, CurrentScreen::Confirming));
+    }
+
+    #[test]
+    fn protected_branches_need_confirming_before_delete() {
+        let mut repo = repo(&["main", "release/1.0"], "main");
+        repo.protected = vec!["release/*".to_string()];
+        let (mut app, git) = app(repo);
+        select(&mut app, "release/1.0");
+
+        let result = app.branches.delete_current(false);
+        assert!(matches!(
+            result,
+            Err(GituiError::ProtectedBranch {
+                action: ProtectedAction::Delete,
+                ..
+            })
+        ));
+        assert!(calls(&git).is_empty());
+
+        app.branches.confirm_protected("release/1.0");
+        app.branches.delete_current(false).unwrap();
+
+        assert_eq!(calls(&git), vec!["branch -d release/1.0"]);
+    }
+
+    #[test]
+    fn merging_into_a_protected_branch_is_refused() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.protected = vec!["main".to_string()];
+        let (mut app, git) = app(repo);
+        select(&mut app, "feature");
+
+        let result = app.merge_current(MergeMode::Default, None);
+
+        assert!(matches!(
+            result,
+            Err(GituiError::ProtectedBranch {
+                action: ProtectedAction::Merge,
+                ..
+            })
+        ));
+        assert!(calls(&git).is_empty());
+    }
+
+    #[test]
+    fn backing_out_of_a_merge_drops_the_protected_confirmation() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.protected = vec!["main".to_string()];
+        let (mut app, git) = app(repo);
+        select(&mut app, "feature");
+
+        app.branches.confirm_protected("main");
+        app.cancel_merge();
+        let result = app.merge_current(MergeMode::Default, None);
+
+        assert!(matches!(
+            result,
+            Err(GituiError::ProtectedBranch {
+                action: ProtectedAction::Merge,
+                ..
+            })
+        ));
+        assert!(calls(&git).is_empty());
+    }
+
+    #[test]
+    fn a_protected_confirmation_lets_one_action_through() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.protected = vec!["main".to_string()];
+        let (mut app, _) = app(repo);
+        select(&mut app, "feature");
+
+        app.branches.confirm_protected("main");
+        app.merge_current(MergeMode::Default, None).unwrap();
+
+        assert!(matches!(
+            app.rebase_current(),
+            Err(GituiError::ProtectedBranch { .. })
+        ));
+    }
+
+    #[test]
+    fn changing_the_repository_is_refused_while_a_job_runs() {
+        let (mut app, git) = app(repo(&["feature", "main"], "main"));
+        select(&mut app, "feature");
+        app.merge_current(MergeMode::Default, None).unwrap();
+        app.operation = Some(Operation::Rebase);
+
+        assert!(matches!(
+            OperationAction::Abort.next_step(&mut app),
+            Err(GituiError::Busy(_))
+        ));
+        assert!(matches!(
+            MergeAction::Abort.run(&mut app),
+            Err(GituiError::Busy(_))
+        ));
+        assert!(matches!(app.ensure_idle(), Err(GituiError::Busy(_))));
+
+        finish_job(&mut app);
+        assert!(app.ensure_idle().is_ok());
+        assert!(calls(&git).iter().all(|call| !call.contains("abort")));
+    }
+
+    #[test]
+    fn merge_conflicts_can_be_aborted() {
+        let mut repo = repo(&["feature", "main"], "main");
+        repo.conflicting = vec!["feature".to_string()];
+        let (mut app, git) = app(repo);
+        select(&mut app, "feature");
+
+        app.merge_current(MergeMode::Default, None).unwrap();
+        finish_job(&mut app);
+        assert!(matches!(app.current_screen, CurrentScreen::Conflicts));
+
+        app.refresh_operation();
+        assert_eq!(app.operation, Some(Operation::Merge));
+
+        MergeAction::Abort.run(&mut app).unwrap();
+
+        assert!(matches!(app.current_screen, CurrentScreen::ListingBranches));
+        assert_eq!(calls(&git).last().unwrap(), "reset --merge");
+    }
+
+    #[test]
+    fn command_next_step_moves_between_screens() {
+        let (mut app, git) = app(repo(&["main"], "main"));
+
+        Command::Branch.next_step(&mut app).unwrap();
+        assert!(matches!(
+            app.current_screen,
+            CurrentScreen::ListingBranchCommands
+        ));
+
+        Command::FetchAll.next_step(&mut app).unwrap();
+        finish_job(&mut app);
+        assert_eq!(calls(&git), vec!["fetch --all --prune"]);
+    }
+
+    #[test]
+    fn open_outside_a_repository_shows_the_start_screen() {
+        let tmp = crate::test_util::TempDir::new("start-screen");
+
+        let mut app = App::open(tmp.0.clone());
+        assert!(matches!(app.current_screen, CurrentScreen::NoRepository));
+
+        app.init_repository().unwrap();
+        assert!(matches!(app.current_screen, CurrentScreen::Main));
+        assert!(app.git.toplevel().is_some());
+    }
+
+    #[test]
+    fn switching_repositories_keeps_each_ones_state() {
+        let tmp = crate::test_util::TempDir::new("workspace");
+        for name in ["api", "web"] {
+            let dir = tmp.0.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            crate::git::GitCli::new(dir, Arc::default()).init().unwrap();
+        }
+        let root = tmp.0.canonicalize().unwrap();
+
+        let mut app = App::open(tmp.0.clone());
+        assert!(matches!(app.current_screen, CurrentScreen::Workspace));
+        assert_eq!(app.workspace.repos.len(), 2);
+
+        app.switch_repository(&root.join("api"));
+        assert_eq!(app.path, root.join("api"));
+        app.search_query = "feature".to_string();
+
+        app.open_workspace();
+        app.switch_repository(&root.join("web"));
+        assert_eq!(app.path, root.join("web"));
+        assert!(app.search_query.is_empty());
+
+        app.open_workspace();
+        app.switch_repository(&root.join("api"));
+        assert!(matches!(app.current_screen, CurrentScreen::Main));
+        assert_eq!(app.search_query, "feature");
+    }
+
+    #[test]
+    fn command_log_lists_newest_first() {
+        let (mut app, _git) = app(repo(&["feature", "main"], "main"));
+        select(&mut app, "feature");
+        app.branches.switch_current().unwrap();
+
+        Command::Log.next_step(&mut app).unwrap();
+
+        assert!(matches!(app.current_screen, CurrentScreen::CommandLog));
+        assert_eq!(
+            app.get_current_log_entry().unwrap().command(),
+            "git switch feature"
+        );
+    }
+
+    #[test]
+    fn cancel_only_reaches_git_while_a_job_runs() {
+        let (mut app, git) = app(repo(&["main"], "main"));
+
+        app.cancel_job();
+        assert!(calls(&git).is_empty());
+
+        app.fetch_all().unwrap();
+        app.cancel_job();
+        finish_job(&mut app);
+
+        assert!(calls(&git).contains(&"cancel".to_string()));
+    }
+
+    #[test]
+    fn only_one_background_job_runs_at_a_time() {
+        let (mut app, _git) = app(repo(&["feature", "main"], "main"));
+        select(&mut app, "feature");
+
+        app.fetch_all().unwrap();
+        assert!(app
+            .jobs
+            .spinner()
+            .unwrap()
+            .ends_with("Fetching all remotes"));
+        assert!(matches!(
+            app.merge_current(MergeMode::Default, None),
+            Err(GituiError::Busy(_))
+        ));
+
+        finish_job(&mut app);
+        assert!(app.jobs.spinner().is_none());
+        app.merge_current(MergeMode::Default, None).unwrap();
+    }
+
+    #[test]
+    fn branch_command_next_step_lists_fresh_branches() {
+        let (mut app, git) = app(repo(&["main"], "main"));
+        git.repo
+            .lock()
+            .unwrap()
+            .branches
+            .push(Branch::new("feature", false));
+        app.search_query = "old".to_string();
+
+        BranchCommand::Delete.next_step(&mut app).unwrap();
+
+        assert!(matches!(app.current_screen, CurrentScreen::ListingBranches));
+        assert!(matches!(
+            app.selected_branch_command,
+            Some(BranchCommand::Delete)
+        ));
+        assert!(app.search_query.is_empty());
+        assert_eq!(app.branches.get_values().len(), 2);
+    }
+
+    #[test]
+    fn operation_action_next_step_finishes_the_operation() {
+        let mut repo = repo(&["main"], "main");
+        repo.operation = Some(Operation::Rebase);
+        let (mut app, git) = app(repo);
+        app.open_operation(String::new());
+        assert!(matches!(app.current_screen, CurrentScreen::Operation));
+
+        OperationAction::Continue.next_step(&mut app).unwrap();
+
+        assert_eq!(calls(&git), vec!["rebase --continue"]);
+        assert_eq!(app.operation, None);
+        assert!(matches!(app.current_screen, CurrentScreen::ListingBranches));
+    }
+}
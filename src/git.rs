@@ -0,0 +1,1439 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    app::{
+        parse_branches, parse_commits, parse_conflicts, parse_recent_checkouts, parse_worktrees,
+        Branch, CommitSummary, Conflict, GituiError, Head, MergeMode, Operation, Side, Worktree,
+        BRANCH_FORMAT, LOG_FORMAT,
+    },
+    workspace::{parse_status, RepoStatus},
+};
+
+mod log;
+#[cfg(feature = "gix")]
+mod native;
+
+pub use log::{CommandLog, LogEntry};
+
+/// The backend for the repository at `dir`, recording into `log`: gitoxide
+/// for reads when built with the `gix` feature and it can open the
+/// repository, the git executable otherwise.
+pub fn open(dir: PathBuf, log: Arc<CommandLog>) -> Arc<dyn GitBackend> {
+    #[cfg(feature = "gix")]
+    if let Ok(backend) = native::GixBackend::open(&dir, log.clone()) {
+        return Arc::new(backend);
+    }
+
+    Arc::new(GitCli::new(dir, log))
+}
+
+/// The root of the work tree `dir` is in, like git finds it for `git -C dir`.
+pub fn discover(dir: &Path, log: Arc<CommandLog>) -> Option<PathBuf> {
+    GitCli::new(dir.to_path_buf(), log)
+        .toplevel()
+        .map(PathBuf::from)
+}
+
+/// Everything gitui asks of git. `GitCli` runs the real thing, tests use
+/// `fake::FakeGit` so `App` and `Branches` can be driven without a repository.
+pub trait GitBackend: Send + Sync {
+    /// Local and remote-tracking branches, see `parse_branches`.
+    fn branches(&self) -> Vec<Branch>;
+    fn head(&self) -> Head;
+    /// The merge, rebase, etc. git stopped in the middle of, if any.
+    fn operation(&self) -> Option<Operation>;
+    /// Runs `git <operation> <arg>`, e.g. `rebase --continue`.
+    fn run_operation(&self, operation: Operation, arg: &str) -> GitOutput;
+    /// The branch `git switch -` would go to, `None` when there is none or
+    /// it was a detached commit.
+    fn previous_branch(&self) -> Option<String>;
+    /// Branch names in the order they were last checked out, most recent first.
+    fn recent_checkouts(&self) -> Vec<String>;
+    /// Patterns of `gitui.protectedBranch` in the repo's config.
+    fn protected_patterns(&self) -> Vec<String>;
+    /// `gitui.workspace` paths: repositories, or directories holding them.
+    fn workspace_paths(&self) -> Vec<String>;
+    /// Checked out branch, dirty state and ahead/behind, for the workspace screen.
+    fn status(&self) -> Option<RepoStatus>;
+    /// Local branches whose tip is reachable from `base`.
+    fn merged_branches(&self, base: &str) -> Result<Vec<String>, GituiError>;
+    /// Commits in `range`, e.g. `main..feature`, newest first.
+    fn commits(&self, range: &str) -> Result<Vec<CommitSummary>, GituiError>;
+    /// `git diff --stat from...to`, what merging `to` into `from` brings in.
+    fn diff_stat(&self, from: &str, to: &str) -> Result<String, GituiError>;
+    /// Root of the current worktree.
+    fn toplevel(&self) -> Option<String>;
+    fn worktrees(&self) -> Vec<Worktree>;
+    /// Adds a worktree at `path` checking out `branch`, or a new branch
+    /// `create` tracking `branch` when set.
+    fn add_worktree(
+        &self,
+        path: &str,
+        branch: &str,
+        create: Option<&str>,
+    ) -> Result<(), GituiError>;
+    fn remove_worktree(&self, path: &str) -> Result<(), GituiError>;
+    fn prune_worktrees(&self) -> Result<(), GituiError>;
+    /// Paths git reports as unmerged, with how they conflict.
+    fn conflicts(&self) -> Vec<Conflict>;
+    /// Checks out one side of a conflicted `path`.
+    fn checkout_side(&self, path: &str, side: Side) -> Result<(), GituiError>;
+    /// Stages `path` as is.
+    fn stage(&self, path: &str) -> Result<(), GituiError>;
+    /// Removes `path` from the index and the working tree.
+    fn remove(&self, path: &str) -> Result<(), GituiError>;
+    /// Runs `git mergetool` on `path`, on whatever terminal gitui runs in.
+    fn mergetool(&self, path: &str) -> Result<(), GituiError>;
+    /// Fetches every remote, pruning remote branches deleted there so
+    /// their local branches show up as gone.
+    fn fetch_all(&self) -> Result<(), GituiError>;
+    fn switch(&self, branch: &str) -> Result<(), GituiError>;
+    /// Creates the local branch `local` tracking `remote_branch` and switches to it.
+    fn switch_tracking(&self, remote_branch: &str, local: &str) -> Result<(), GituiError>;
+    /// Creates `name` from `start_point`, HEAD when `None`, switching to it if `switch`.
+    fn create_branch(
+        &self,
+        name: &str,
+        start_point: Option<&str>,
+        switch: bool,
+    ) -> Result<(), GituiError>;
+    /// Merges `branch` into the checked out one. Stopping on conflicts
+    /// returns `GituiError::MergeConflict`.
+    fn merge(&self, branch: &str, mode: MergeMode, message: Option<&str>)
+        -> Result<(), GituiError>;
+    /// Commits the index, with git's prepared message when `message` is `None`.
+    fn commit(&self, message: Option<&str>) -> Result<(), GituiError>;
+    /// Throws away an in-progress merge, squash merges included.
+    fn abort_merge(&self) -> Result<(), GituiError>;
+    /// Rebases the checked out branch onto `onto`. Stopping midway returns
+    /// `GituiError::RebaseStopped`.
+    fn rebase(&self, onto: &str) -> Result<(), GituiError>;
+    /// `git branch -d`, or `-D` when `force` is set. A safe delete of an
+    /// unmerged branch returns `GituiError::BranchNotMerged`.
+    fn delete_branch(&self, name: &str, force: bool) -> Result<(), GituiError>;
+    fn rename_branch(&self, name: &str, new_name: &str) -> Result<(), GituiError>;
+    /// Creates a new, empty repository in the directory this backend runs in.
+    fn init(&self) -> Result<(), GituiError>;
+
+    /// Kills the git commands running right now, their output ends up
+    /// with `killed` set to `Killed::Cancelled`.
+    fn cancel(&self);
+    /// Every git invocation so far (up to a limit), oldest first.
+    fn command_log(&self) -> Vec<LogEntry> {
+        self.log().entries()
+    }
+    /// Where this backend records git invocations, shared with the
+    /// backends `open` and `discover` make for the same app.
+    fn log(&self) -> Arc<CommandLog>;
+}
+
+/// What one git invocation did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitOutput {
+    pub argv: Vec<String>,
+    /// Exit code, `None` when git couldn't be started or was killed by a signal.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Set when gitui killed git instead of letting it finish.
+    pub killed: Option<Killed>,
+}
+
+/// Why gitui killed a git command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Killed {
+    /// `GitBackend::cancel`, e.g. the user pressed the cancel key.
+    Cancelled,
+    /// It ran longer than `gitui.timeout`.
+    TimedOut(Duration),
+}
+
+impl GitOutput {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    /// Passes a successful output through, otherwise makes an error saying
+    /// it failed `to` do something, with the exit code and stderr.
+    /// A killed git becomes `GituiError::Cancelled` or `GituiError::TimedOut`
+    /// instead, it didn't get the chance to fail.
+    pub fn check(self, err: fn(String) -> GituiError, to: &str) -> Result<Self, GituiError> {
+        match self.killed {
+            Some(Killed::Cancelled) => return Err(GituiError::Cancelled(self.argv.join(" "))),
+            Some(Killed::TimedOut(after)) => {
+                return Err(GituiError::TimedOut {
+                    command: self.argv.join(" "),
+                    after,
+                })
+            }
+            None if self.success() => return Ok(self),
+            None => {}
+        }
+
+        Err(err(format!(
+            "failed to {} ({}). output: {}",
+            to,
+            match self.status {
+                Some(code) => format!("git exited with {}", code),
+                None => "git didn't exit normally".to_string(),
+            },
+            self.stderr.trim()
+        )))
+    }
+}
+
+/// How soon a running git is first checked for having exited, been
+/// cancelled or run out of time. Most commands are done within a few
+/// milliseconds, so the checks start often and back off up to `POLL`.
+const FIRST_POLL: Duration = Duration::from_millis(1);
+const POLL: Duration = Duration::from_millis(50);
+/// How long git gets to clean up (e.g. remove `index.lock`) after SIGTERM
+/// before it's killed outright.
+const GRACE: Duration = Duration::from_secs(1);
+
+/// `GitBackend` running the `git` executable in `dir`.
+pub struct GitCli {
+    dir: PathBuf,
+    /// From `gitui.timeout`, in seconds. Applies to each git command.
+    timeout: Option<Duration>,
+    /// Cancel flags of the commands running right now, see `GitCli::cancel`.
+    running: Mutex<Vec<Arc<AtomicBool>>>,
+    log: Arc<CommandLog>,
+}
+
+impl GitCli {
+    pub fn new(dir: PathBuf, log: Arc<CommandLog>) -> Self {
+        let mut cli = Self {
+            dir,
+            timeout: None,
+            running: Mutex::new(Vec::new()),
+            log,
+        };
+
+        cli.timeout = cli
+            .stdout(&["config", "--get", "gitui.timeout"])
+            .trim()
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+
+        cli
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.current_dir(&self.dir);
+        cmd
+    }
+
+    /// Runs git with `args`. Every call to git goes through here.
+    fn exec(&self, args: &[&str]) -> GitOutput {
+        self.exec_with(self.command(), args)
+    }
+
+    fn exec_with(&self, cmd: Command, args: &[&str]) -> GitOutput {
+        let started = Instant::now();
+        let output = self.spawn(cmd, args);
+
+        self.log.push(LogEntry {
+            argv: output.argv.clone(),
+            cwd: self.dir.clone(),
+            status: output.status,
+            killed: output.killed,
+            duration: started.elapsed(),
+            stdout: output.stdout.clone(),
+            stderr: output.stderr.clone(),
+        });
+
+        output
+    }
+
+    fn spawn(&self, mut cmd: Command, args: &[&str]) -> GitOutput {
+        let argv = std::iter::once("git")
+            .chain(args.iter().copied())
+            .map(|a| a.to_string())
+            .collect();
+
+        cmd.args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // its own process group, so killing it takes ssh, hooks, etc. along
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                return GitOutput {
+                    argv,
+                    status: None,
+                    stdout: String::new(),
+                    stderr: format!("couldnt run git: {}", err),
+                    killed: None,
+                }
+            }
+        };
+
+        // read both pipes while waiting, git blocks once one of them is full
+        let stdout = read_to_string(child.stdout.take());
+        let stderr = read_to_string(child.stderr.take());
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.running.lock().unwrap().push(cancelled.clone());
+
+        let (status, killed) = self.wait(&mut child, &cancelled);
+
+        self.running
+            .lock()
+            .unwrap()
+            .retain(|c| !Arc::ptr_eq(c, &cancelled));
+
+        GitOutput {
+            argv,
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+            killed,
+        }
+    }
+
+    /// Waits for `child` to exit, terminating it once it's cancelled or runs
+    /// past the timeout.
+    fn wait(&self, child: &mut Child, cancelled: &AtomicBool) -> (Option<i32>, Option<Killed>) {
+        let started = Instant::now();
+        let mut killed = None;
+        let mut terminated = None;
+        let mut poll = FIRST_POLL;
+
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => return (status.code(), killed),
+                Ok(None) => {}
+                Err(_) => return (None, killed),
+            }
+
+            if killed.is_none() {
+                if cancelled.load(Ordering::Relaxed) {
+                    killed = Some(Killed::Cancelled);
+                } else if let Some(timeout) = self.timeout.filter(|t| started.elapsed() > *t) {
+                    killed = Some(Killed::TimedOut(timeout));
+                }
+
+                if killed.is_some() {
+                    signal(child, Signal::Terminate);
+                    terminated = Some(Instant::now());
+                }
+            }
+
+            if terminated.is_some_and(|at| at.elapsed() > GRACE) {
+                signal(child, Signal::Kill);
+                terminated = None;
+            }
+
+            std::thread::sleep(poll);
+            poll = (poll * 2).min(POLL);
+        }
+    }
+
+    /// stdout of a successful run, empty otherwise.
+    fn stdout(&self, args: &[&str]) -> String {
+        let output = self.exec(args);
+        if output.success() {
+            output.stdout
+        } else {
+            String::new()
+        }
+    }
+
+    /// Absolute path of the `.git` directory, of the worktree's own one in
+    /// linked worktrees.
+    fn git_dir(&self) -> Option<PathBuf> {
+        let output = self.exec(&["rev-parse", "--absolute-git-dir"]);
+
+        output
+            .success()
+            .then(|| PathBuf::from(output.stdout.trim()))
+    }
+
+    /// A squash merge is only told apart by the `SQUASH_MSG` it leaves for
+    /// the commit, or by unmerged paths with no other operation to blame.
+    fn squash_in_progress(&self, git_dir: &Path) -> bool {
+        git_dir.join("SQUASH_MSG").exists() || !self.stdout(&["ls-files", "--unmerged"]).is_empty()
+    }
+
+    /// `SQUASH_MSG` of the squash merge in progress, if there is one.
+    fn squash_message(&self) -> Option<PathBuf> {
+        self.git_dir()
+            .map(|dir| dir.join("SQUASH_MSG"))
+            .filter(|path| path.exists())
+    }
+
+    /// Replaces the `SQUASH_MSG` git left behind, which the squash commit
+    /// takes its message from, whether it's made right away or after
+    /// resolving conflicts.
+    fn write_squash_message(&self, message: &str) -> Result<(), GituiError> {
+        let Some(path) = self.squash_message() else {
+            return Ok(());
+        };
+
+        std::fs::write(path, message).map_err(|err| {
+            GituiError::BranchMerge(format!("couldn't write the squash message: {}", err))
+        })
+    }
+
+    /// Commits with the message git prepared. For squash merges that's
+    /// `SQUASH_MSG` alone, `--no-edit` would add the conflict list git
+    /// keeps in `MERGE_MSG`.
+    fn commit_prepared(&self) -> GitOutput {
+        match self.squash_message() {
+            Some(path) => self.exec(&["commit", "--file", &path.to_string_lossy()]),
+            None => self.exec(&["commit", "--no-edit"]),
+        }
+    }
+
+    /// Whether `git branch -d` would delete the local branch `name`, i.e. it's
+    /// merged into its upstream, or into HEAD when it has none. Asked up front
+    /// since git only says why it refused in the user's language.
+    fn is_merged(&self, name: &str) -> bool {
+        let branch = format!("refs/heads/{}", name);
+        let upstream = format!("{}@{{upstream}}", branch);
+        let base = if self
+            .exec(&["rev-parse", "--verify", "--quiet", &upstream])
+            .success()
+        {
+            upstream.as_str()
+        } else {
+            "HEAD"
+        };
+
+        // exits 1 when it isn't an ancestor, anything else is left to `branch -d`
+        self.exec(&["merge-base", "--is-ancestor", &branch, base])
+            .status
+            != Some(1)
+    }
+
+    /// Runs git with `args`, turning a failure into the error made by `err`.
+    fn run(&self, args: &[&str], err: fn(String) -> GituiError) -> Result<(), GituiError> {
+        self.exec(args)
+            .check(err, &format!("run git {}", args.join(" ")))
+            .map(|_| ())
+    }
+}
+
+enum Signal {
+    Terminate,
+    Kill,
+}
+
+/// Sends `signal` to git and everything in its process group, so children
+/// like hooks or aliases holding on to the pipes go too.
+fn signal(child: &mut Child, signal: Signal) {
+    #[cfg(unix)]
+    // SAFETY: plain syscall, a negative pid addresses the process group
+    // `exec_with` put git in
+    unsafe {
+        let signal = match signal {
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        };
+        libc::kill(-(child.id() as libc::pid_t), signal);
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = signal;
+        let _ = child.kill();
+    }
+}
+
+fn read_to_string(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        String::from_utf8_lossy(&buf).to_string()
+    })
+}
+
+impl GitBackend for GitCli {
+    fn branches(&self) -> Vec<Branch> {
+        parse_branches(&self.stdout(&[
+            "for-each-ref",
+            &format!("--format={}", BRANCH_FORMAT),
+            "refs/heads",
+            "refs/remotes",
+        ]))
+    }
+
+    fn head(&self) -> Head {
+        let branch = self.exec(&["symbolic-ref", "--quiet", "--short", "HEAD"]);
+        if branch.success() {
+            return Head::Branch(branch.stdout.trim().to_string());
+        }
+
+        let commit = self.exec(&["rev-parse", "--short", "HEAD"]);
+        if commit.success() {
+            Head::Detached(commit.stdout.trim().to_string())
+        } else {
+            Head::Unknown
+        }
+    }
+
+    /// Looks for the state files git leaves in its directory while an
+    /// operation is in progress.
+    fn operation(&self) -> Option<Operation> {
+        let git_dir = self.git_dir()?;
+
+        [
+            ("rebase-merge", Operation::Rebase),
+            ("rebase-apply", Operation::Rebase),
+            ("MERGE_HEAD", Operation::Merge),
+            ("CHERRY_PICK_HEAD", Operation::CherryPick),
+            ("REVERT_HEAD", Operation::Revert),
+            ("BISECT_LOG", Operation::Bisect),
+        ]
+        .into_iter()
+        .find(|(marker, _)| git_dir.join(marker).exists())
+        .map(|(_, operation)| operation)
+        .or_else(|| {
+            self.squash_in_progress(&git_dir)
+                .then_some(Operation::SquashMerge)
+        })
+    }
+
+    fn run_operation(&self, operation: Operation, arg: &str) -> GitOutput {
+        // there's no squash merge for `git merge` to continue or abort
+        match (operation, arg) {
+            (Operation::SquashMerge, "--continue") => return self.commit_prepared(),
+            (Operation::SquashMerge, _) => return self.exec(&["reset", "--merge"]),
+            _ => {}
+        }
+
+        let mut cmd = self.command();
+        // don't open an editor for the commit message on continue
+        cmd.env("GIT_EDITOR", "true");
+
+        self.exec_with(cmd, &[operation.command(), arg])
+    }
+
+    fn previous_branch(&self) -> Option<String> {
+        self.stdout(&["rev-parse", "--symbolic-full-name", "@{-1}"])
+            .trim()
+            .strip_prefix("refs/heads/")
+            .map(|name| name.to_string())
+    }
+
+    fn recent_checkouts(&self) -> Vec<String> {
+        let reflog = self.stdout(&["reflog", "show", "--format=%gs", "HEAD", "--"]);
+        let branches: Vec<String> = self
+            .stdout(&["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+
+        parse_recent_checkouts(&reflog, &branches)
+    }
+
+    fn protected_patterns(&self) -> Vec<String> {
+        self.stdout(&["config", "--get-all", "gitui.protectedBranch"])
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+
+    fn workspace_paths(&self) -> Vec<String> {
+        self.stdout(&["config", "--type=path", "--get-all", "gitui.workspace"])
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+
+    fn status(&self) -> Option<RepoStatus> {
+        let output = self.exec(&["status", "--porcelain=v2", "--branch"]);
+
+        output.success().then(|| parse_status(&output.stdout))
+    }
+
+    fn merged_branches(&self, base: &str) -> Result<Vec<String>, GituiError> {
+        let output = self
+            .exec(&["branch", "--format=%(refname:short)", "--merged", base])
+            .check(
+                GituiError::BranchCleanUp,
+                &format!("find branches merged into {}", base),
+            )?;
+
+        Ok(output.stdout.lines().map(|l| l.to_string()).collect())
+    }
+
+    fn commits(&self, range: &str) -> Result<Vec<CommitSummary>, GituiError> {
+        let output = self
+            .exec(&["log", &format!("--format={}", LOG_FORMAT), range, "--"])
+            .check(GituiError::Compare, &format!("list commits in {}", range))?;
+
+        Ok(parse_commits(&output.stdout))
+    }
+
+    fn diff_stat(&self, from: &str, to: &str) -> Result<String, GituiError> {
+        let output = self
+            .exec(&["diff", "--stat", &format!("{}...{}", from, to), "--"])
+            .check(GituiError::Compare, &format!("diff {} and {}", to, from))?;
+
+        Ok(output.stdout)
+    }
+
+    fn toplevel(&self) -> Option<String> {
+        let output = self.exec(&["rev-parse", "--show-toplevel"]);
+
+        output.success().then(|| output.stdout.trim().to_string())
+    }
+
+    fn worktrees(&self) -> Vec<Worktree> {
+        parse_worktrees(
+            &self.stdout(&["worktree", "list", "--porcelain"]),
+            self.toplevel().as_deref(),
+        )
+    }
+
+    fn add_worktree(
+        &self,
+        path: &str,
+        branch: &str,
+        create: Option<&str>,
+    ) -> Result<(), GituiError> {
+        match create {
+            Some(local) => self.run(
+                &["worktree", "add", "--track", "-b", local, path, branch],
+                GituiError::Worktree,
+            ),
+            None => self.run(&["worktree", "add", path, branch], GituiError::Worktree),
+        }
+    }
+
+    fn remove_worktree(&self, path: &str) -> Result<(), GituiError> {
+        self.run(&["worktree", "remove", path], GituiError::Worktree)
+    }
+
+    fn prune_worktrees(&self) -> Result<(), GituiError> {
+        self.run(&["worktree", "prune"], GituiError::Worktree)
+    }
+
+    fn conflicts(&self) -> Vec<Conflict> {
+        parse_conflicts(&self.stdout(&["status", "--porcelain=v1", "-z"]))
+    }
+
+    fn checkout_side(&self, path: &str, side: Side) -> Result<(), GituiError> {
+        let side = match side {
+            Side::Ours => "--ours",
+            Side::Theirs => "--theirs",
+        };
+
+        self.run(&["checkout", side, "--", path], GituiError::Conflict)
+    }
+
+    fn stage(&self, path: &str) -> Result<(), GituiError> {
+        self.run(&["add", "--", path], GituiError::Conflict)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), GituiError> {
+        self.run(&["rm", "--quiet", "--", path], GituiError::Conflict)
+    }
+
+    fn mergetool(&self, path: &str) -> Result<(), GituiError> {
+        // not `exec`, the tool needs the terminal
+        let args = ["mergetool", "--no-prompt", "--", path];
+        let started = Instant::now();
+        let status = self.command().args(args).status();
+
+        self.log.push(LogEntry {
+            argv: std::iter::once("git")
+                .chain(args)
+                .map(|a| a.to_string())
+                .collect(),
+            cwd: self.dir.clone(),
+            status: status.as_ref().ok().and_then(|s| s.code()),
+            killed: None,
+            duration: started.elapsed(),
+            // went to the terminal
+            stdout: String::new(),
+            stderr: String::new(),
+        });
+
+        match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(GituiError::Conflict(format!(
+                "git mergetool exited with {} for {}",
+                status, path
+            ))),
+            Err(err) => Err(GituiError::Conflict(format!(
+                "couldnt run git mergetool: {}",
+                err
+            ))),
+        }
+    }
+
+    fn fetch_all(&self) -> Result<(), GituiError> {
+        self.exec(&["fetch", "--all", "--prune"])
+            .check(GituiError::FetchAll, "fetch all")
+            .map(|_| ())
+    }
+
+    fn switch(&self, branch: &str) -> Result<(), GituiError> {
+        self.exec(&["switch", branch])
+            .check(GituiError::BranchSwitch, &format!("switch to {}", branch))
+            .map(|_| ())
+    }
+
+    fn switch_tracking(&self, remote_branch: &str, local: &str) -> Result<(), GituiError> {
+        self.exec(&["switch", "--create", local, "--track", remote_branch])
+            .check(
+                GituiError::BranchSwitch,
+                &format!("switch to {} tracking {}", local, remote_branch),
+            )
+            .map(|_| ())
+    }
+
+    fn create_branch(
+        &self,
+        name: &str,
+        start_point: Option<&str>,
+        switch: bool,
+    ) -> Result<(), GituiError> {
+        let mut args = if switch {
+            vec!["switch", "--create", name]
+        } else {
+            vec!["branch", name]
+        };
+        args.extend(start_point);
+
+        self.exec(&args)
+            .check(GituiError::BranchCreate, &format!("create branch {}", name))
+            .map(|_| ())
+    }
+
+    fn merge(
+        &self,
+        branch: &str,
+        mode: MergeMode,
+        message: Option<&str>,
+    ) -> Result<(), GituiError> {
+        let mut args = vec!["merge"];
+        args.extend(mode.arg());
+        // a squash merge doesn't commit, the message goes to `git commit` below
+        if let (Some(message), false) = (message, mode == MergeMode::Squash) {
+            args.extend(["-m", message]);
+        }
+        args.push(branch);
+
+        let output = self.exec(&args);
+
+        if let (MergeMode::Squash, Some(message)) = (mode, message) {
+            self.write_squash_message(message)?;
+        }
+
+        // git exits non-zero when it stops on conflicts, that's not a failure here
+        if output.killed.is_none()
+            && (self.operation() == Some(Operation::Merge)
+                || (mode == MergeMode::Squash && !self.conflicts().is_empty()))
+        {
+            return Err(GituiError::MergeConflict(output.stdout));
+        }
+
+        output.check(GituiError::BranchMerge, &format!("merge {}", branch))?;
+
+        if mode == MergeMode::Squash {
+            self.commit_prepared()
+                .check(GituiError::BranchMerge, "commit squash merge")?;
+        }
+
+        Ok(())
+    }
+
+    fn commit(&self, message: Option<&str>) -> Result<(), GituiError> {
+        match message {
+            Some(message) => self.exec(&["commit", "-m", message]),
+            None => self.commit_prepared(),
+        }
+        .check(GituiError::BranchMerge, "commit merge")
+        .map(|_| ())
+    }
+
+    fn abort_merge(&self) -> Result<(), GituiError> {
+        // unlike `merge --abort` this also works for squash merges
+        self.exec(&["reset", "--merge"])
+            .check(GituiError::BranchMerge, "abort merge")
+            .map(|_| ())
+    }
+
+    fn rebase(&self, onto: &str) -> Result<(), GituiError> {
+        let output = self.exec(&["rebase", onto]);
+
+        if !output.success()
+            && output.killed.is_none()
+            && self.operation() == Some(Operation::Rebase)
+        {
+            return Err(GituiError::RebaseStopped(output.stderr));
+        }
+
+        output
+            .check(GituiError::Rebase, &format!("rebase onto {}", onto))
+            .map(|_| ())
+    }
+
+    fn delete_branch(&self, name: &str, force: bool) -> Result<(), GituiError> {
+        if !force && !self.is_merged(name) {
+            return Err(GituiError::BranchNotMerged(name.to_string()));
+        }
+
+        self.exec(&["branch", if force { "-D" } else { "-d" }, name])
+            .check(GituiError::BranchDelete, &format!("delete branch {}", name))
+            .map(|_| ())
+    }
+
+    fn rename_branch(&self, name: &str, new_name: &str) -> Result<(), GituiError> {
+        self.exec(&["branch", "-m", name, new_name])
+            .check(
+                GituiError::BranchRename,
+                &format!("rename branch {} to {}", name, new_name),
+            )
+            .map(|_| ())
+    }
+
+    fn init(&self) -> Result<(), GituiError> {
+        self.run(&["init", "--quiet"], GituiError::Repository)
+    }
+
+    fn cancel(&self) {
+        for cancelled in self.running.lock().unwrap().iter() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn log(&self) -> Arc<CommandLog> {
+        self.log.clone()
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{CommandLog, GitBackend, GitOutput, LogEntry};
+    use crate::{
+        app::{
+            Branch, CommitSummary, Conflict, GituiError, Head, MergeMode, Operation, Side, Worktree,
+        },
+        workspace::RepoStatus,
+    };
+
+    /// What `FakeGit` pretends the repository looks like. Tests set it up
+    /// directly and check it afterwards.
+    #[derive(Default)]
+    pub struct FakeRepo {
+        pub branches: Vec<Branch>,
+        /// Set when HEAD is detached, at this commit.
+        pub detached: Option<String>,
+        pub operation: Option<Operation>,
+        pub conflicts: Vec<Conflict>,
+        pub worktrees: Vec<Worktree>,
+        pub protected: Vec<String>,
+        pub workspace: Vec<String>,
+        /// Branches a safe delete refuses, as if they weren't merged.
+        pub unmerged: Vec<String>,
+        /// Branch names checked out so far, most recent last.
+        pub checkouts: Vec<String>,
+        /// Branches merging which stops on conflicts.
+        pub conflicting: Vec<String>,
+        /// Every call that changes the repository, e.g. `switch main`.
+        pub calls: Vec<String>,
+    }
+
+    impl FakeRepo {
+        fn checked_out(&self) -> Option<&Branch> {
+            self.branches.iter().find(|b| b.is_checked_out)
+        }
+
+        fn local(&self, name: &str) -> Option<usize> {
+            self.branches
+                .iter()
+                .position(|b| b.remote.is_none() && b.name == name)
+        }
+
+        fn check_out(&mut self, name: &str) {
+            for b in self.branches.iter_mut() {
+                b.is_checked_out = b.remote.is_none() && b.name == name;
+            }
+            self.detached = None;
+            self.checkouts.push(name.to_string());
+        }
+    }
+
+    /// In-memory `GitBackend`, for testing without a repository.
+    #[derive(Default)]
+    pub struct FakeGit {
+        pub repo: Mutex<FakeRepo>,
+    }
+
+    impl FakeGit {
+        pub fn new(repo: FakeRepo) -> Self {
+            Self {
+                repo: Mutex::new(repo),
+            }
+        }
+
+        fn repo(&self) -> std::sync::MutexGuard<'_, FakeRepo> {
+            self.repo.lock().unwrap()
+        }
+
+        fn record(&self, call: String) {
+            self.repo().calls.push(call);
+        }
+
+        fn call(&self, call: String) -> std::sync::MutexGuard<'_, FakeRepo> {
+            let mut repo = self.repo();
+            repo.calls.push(call);
+            repo
+        }
+    }
+
+    impl GitBackend for FakeGit {
+        fn branches(&self) -> Vec<Branch> {
+            self.repo().branches.iter().map(Branch::from).collect()
+        }
+
+        fn head(&self) -> Head {
+            let repo = self.repo();
+            match (&repo.detached, repo.checked_out()) {
+                (Some(id), _) => Head::Detached(id.to_string()),
+                (None, Some(b)) => Head::Branch(b.name.to_string()),
+                (None, None) => Head::Unknown,
+            }
+        }
+
+        fn operation(&self) -> Option<Operation> {
+            self.repo().operation
+        }
+
+        fn run_operation(&self, operation: Operation, arg: &str) -> GitOutput {
+            let mut repo = self.call(format!("{} {}", operation.command(), arg));
+            repo.operation = None;
+            repo.conflicts.clear();
+            GitOutput {
+                argv: vec![
+                    "git".to_string(),
+                    operation.command().to_string(),
+                    arg.to_string(),
+                ],
+                status: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+                killed: None,
+            }
+        }
+
+        fn previous_branch(&self) -> Option<String> {
+            let repo = self.repo();
+            repo.checkouts.iter().rev().nth(1).cloned()
+        }
+
+        fn recent_checkouts(&self) -> Vec<String> {
+            let mut recent: Vec<String> = Vec::new();
+            for name in self.repo().checkouts.iter().rev() {
+                if !recent.contains(name) {
+                    recent.push(name.to_string());
+                }
+            }
+            recent
+        }
+
+        fn protected_patterns(&self) -> Vec<String> {
+            self.repo().protected.clone()
+        }
+
+        fn workspace_paths(&self) -> Vec<String> {
+            self.repo().workspace.clone()
+        }
+
+        fn status(&self) -> Option<RepoStatus> {
+            let repo = self.repo();
+            Some(RepoStatus {
+                branch: repo.checked_out().map(|b| b.name.to_string()),
+                dirty: false,
+                upstream: None,
+            })
+        }
+
+        fn merged_branches(&self, _base: &str) -> Result<Vec<String>, GituiError> {
+            let repo = self.repo();
+            Ok(repo
+                .branches
+                .iter()
+                .filter(|b| b.remote.is_none() && !repo.unmerged.contains(&b.name))
+                .map(|b| b.name.to_string())
+                .collect())
+        }
+
+        fn commits(&self, _range: &str) -> Result<Vec<CommitSummary>, GituiError> {
+            Ok(Vec::new())
+        }
+
+        fn diff_stat(&self, _from: &str, _to: &str) -> Result<String, GituiError> {
+            Ok(String::new())
+        }
+
+        fn toplevel(&self) -> Option<String> {
+            Some("/repo".to_string())
+        }
+
+        fn worktrees(&self) -> Vec<Worktree> {
+            self.repo().worktrees.clone()
+        }
+
+        fn add_worktree(
+            &self,
+            path: &str,
+            branch: &str,
+            create: Option<&str>,
+        ) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("worktree add {} {}", path, branch));
+            if let Some(local) = create {
+                repo.branches.push(Branch::new(local, false));
+            }
+            repo.worktrees.push(Worktree {
+                path: path.to_string(),
+                branch: Some(create.unwrap_or(branch).to_string()),
+                head: String::new(),
+                is_bare: false,
+                is_locked: false,
+                is_prunable: false,
+                is_current: false,
+            });
+            Ok(())
+        }
+
+        fn remove_worktree(&self, path: &str) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("worktree remove {}", path));
+            repo.worktrees.retain(|w| w.path != path);
+            Ok(())
+        }
+
+        fn prune_worktrees(&self) -> Result<(), GituiError> {
+            let mut repo = self.call("worktree prune".to_string());
+            repo.worktrees.retain(|w| !w.is_prunable);
+            Ok(())
+        }
+
+        fn conflicts(&self) -> Vec<Conflict> {
+            self.repo().conflicts.clone()
+        }
+
+        fn checkout_side(&self, path: &str, side: Side) -> Result<(), GituiError> {
+            self.record(format!("checkout {:?} {}", side, path));
+            Ok(())
+        }
+
+        fn stage(&self, path: &str) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("add {}", path));
+            repo.conflicts.retain(|c| c.path != path);
+            Ok(())
+        }
+
+        fn remove(&self, path: &str) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("rm {}", path));
+            repo.conflicts.retain(|c| c.path != path);
+            Ok(())
+        }
+
+        fn mergetool(&self, path: &str) -> Result<(), GituiError> {
+            self.record(format!("mergetool {}", path));
+            Ok(())
+        }
+
+        fn fetch_all(&self) -> Result<(), GituiError> {
+            self.record("fetch --all --prune".to_string());
+            Ok(())
+        }
+
+        fn switch(&self, branch: &str) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("switch {}", branch));
+            if repo.local(branch).is_none() {
+                return Err(GituiError::BranchSwitch(format!(
+                    "invalid reference: {}",
+                    branch
+                )));
+            }
+            repo.check_out(branch);
+            Ok(())
+        }
+
+        fn switch_tracking(&self, remote_branch: &str, local: &str) -> Result<(), GituiError> {
+            let mut repo = self.call(format!(
+                "switch --create {} --track {}",
+                local, remote_branch
+            ));
+            repo.branches.push(Branch::new(local, false));
+            repo.check_out(local);
+            Ok(())
+        }
+
+        fn create_branch(
+            &self,
+            name: &str,
+            start_point: Option<&str>,
+            switch: bool,
+        ) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("branch {} {}", name, start_point.unwrap_or("HEAD")));
+            if repo.local(name).is_some() {
+                return Err(GituiError::BranchCreate(format!(
+                    "a branch named '{}' already exists",
+                    name
+                )));
+            }
+            repo.branches.push(Branch::new(name, false));
+            if switch {
+                repo.check_out(name);
+            }
+            Ok(())
+        }
+
+        fn merge(
+            &self,
+            branch: &str,
+            mode: MergeMode,
+            _message: Option<&str>,
+        ) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("merge {:?} {}", mode, branch));
+            if repo.conflicting.iter().any(|b| b == branch) {
+                repo.operation = Some(Operation::Merge);
+                return Err(GituiError::MergeConflict(String::new()));
+            }
+            Ok(())
+        }
+
+        fn commit(&self, message: Option<&str>) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("commit {}", message.unwrap_or("--no-edit")));
+            repo.operation = None;
+            Ok(())
+        }
+
+        fn abort_merge(&self) -> Result<(), GituiError> {
+            let mut repo = self.call("reset --merge".to_string());
+            repo.operation = None;
+            repo.conflicts.clear();
+            Ok(())
+        }
+
+        fn rebase(&self, onto: &str) -> Result<(), GituiError> {
+            self.record(format!("rebase {}", onto));
+            Ok(())
+        }
+
+        fn delete_branch(&self, name: &str, force: bool) -> Result<(), GituiError> {
+            let mut repo = self.call(format!(
+                "branch {} {}",
+                if force { "-D" } else { "-d" },
+                name
+            ));
+            if !force && repo.unmerged.iter().any(|b| b == name) {
+                return Err(GituiError::BranchNotMerged(name.to_string()));
+            }
+            match repo.local(name) {
+                Some(i) => {
+                    repo.branches.remove(i);
+                    Ok(())
+                }
+                None => Err(GituiError::BranchDelete(format!(
+                    "branch '{}' not found",
+                    name
+                ))),
+            }
+        }
+
+        fn rename_branch(&self, name: &str, new_name: &str) -> Result<(), GituiError> {
+            let mut repo = self.call(format!("branch -m {} {}", name, new_name));
+            match repo.local(name) {
+                Some(i) => {
+                    repo.branches[i].name = new_name.to_string();
+                    Ok(())
+                }
+                None => Err(GituiError::BranchRename(format!(
+                    "branch '{}' not found",
+                    name
+                ))),
+            }
+        }
+
+        fn init(&self) -> Result<(), GituiError> {
+            self.record("init --quiet".to_string());
+            Ok(())
+        }
+
+        fn cancel(&self) {
+            self.record("cancel".to_string());
+        }
+
+        /// Fake calls aren't git invocations, they're in `command_log`.
+        fn log(&self) -> Arc<CommandLog> {
+            Arc::default()
+        }
+
+        /// `calls` as if each had been a successful git invocation.
+        fn command_log(&self) -> Vec<LogEntry> {
+            self.repo()
+                .calls
+                .iter()
+                .map(|call| LogEntry {
+                    argv: std::iter::once("git")
+                        .chain(call.split(' '))
+                        .map(|a| a.to_string())
+                        .collect(),
+                    cwd: PathBuf::new(),
+                    status: Some(0),
+                    killed: None,
+                    duration: Duration::ZERO,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn output(status: Option<i32>, stderr: &str) -> GitOutput {
+        GitOutput {
+            argv: vec!["git".to_string(), "switch".to_string(), "nope".to_string()],
+            status,
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            killed: None,
+        }
+    }
+
+    #[test]
+    fn check_passes_successful_output_through() {
+        let ok = output(Some(0), "Switched to branch 'nope'\n");
+
+        assert_eq!(
+            ok.clone()
+                .check(GituiError::BranchSwitch, "switch")
+                .unwrap(),
+            ok
+        );
+    }
+
+    #[test]
+    fn check_puts_exit_code_and_stderr_in_the_error() {
+        let err = output(Some(128), "fatal: invalid reference: nope\n")
+            .check(GituiError::BranchSwitch, "switch to nope")
+            .unwrap_err();
+
+        assert!(matches!(err, GituiError::BranchSwitch(_)));
+        assert_eq!(
+            err.to_string(),
+            "failed to switch to nope (git exited with 128). output: fatal: invalid reference: nope"
+        );
+    }
+
+    #[test]
+    fn check_reports_killed_git_apart_from_failures() {
+        let mut cancelled = output(None, "");
+        cancelled.killed = Some(Killed::Cancelled);
+        let mut timed_out = output(None, "");
+        timed_out.killed = Some(Killed::TimedOut(Duration::from_secs(30)));
+
+        let err = cancelled
+            .check(GituiError::BranchSwitch, "switch")
+            .unwrap_err();
+        assert!(matches!(err, GituiError::Cancelled(_)));
+        assert_eq!(err.to_string(), "cancelled git switch nope");
+
+        let err = timed_out
+            .check(GituiError::BranchSwitch, "switch")
+            .unwrap_err();
+        assert!(matches!(err, GituiError::TimedOut { .. }));
+        assert_eq!(err.to_string(), "git switch nope timed out after 30s");
+    }
+
+    #[test]
+    fn exec_logs_every_invocation() {
+        let dir = std::env::temp_dir();
+        let cli = GitCli::new(dir.clone(), Arc::default());
+
+        cli.exec(&["--version"]);
+        cli.exec(&["no-such-command"]);
+
+        let log = cli.command_log();
+        let [.., version, failed] = log.as_slice() else {
+            panic!("expected two entries, got {:?}", log);
+        };
+
+        assert_eq!(version.command(), "git --version");
+        assert_eq!(version.cwd, dir);
+        assert_eq!(version.status, Some(0));
+        assert!(version.stdout.starts_with("git version"));
+
+        assert_eq!(failed.outcome(), "exit 1");
+        assert!(failed.stderr.contains("no-such-command"));
+    }
+
+    /// A new repository with an identity to commit and merge with.
+    fn init_with_identity(cli: &GitCli) {
+        cli.init().unwrap();
+        cli.exec(&["config", "user.name", "Ann"]);
+        cli.exec(&["config", "user.email", "ann@example.com"]);
+    }
+
+    #[test]
+    fn delete_branch_refuses_unmerged_branches() {
+        let tmp = TempDir::new("delete-unmerged");
+        let cli = GitCli::new(tmp.0.clone(), Arc::default());
+        let commit = |message| cli.exec(&["commit", "-q", "--allow-empty", "-m", message]);
+
+        init_with_identity(&cli);
+        commit("first");
+        cli.exec(&["switch", "-q", "-c", "feature"]);
+        commit("unmerged");
+        cli.exec(&["switch", "-q", "-"]);
+
+        assert!(matches!(
+            cli.delete_branch("feature", false),
+            Err(GituiError::BranchNotMerged(name)) if name == "feature"
+        ));
+        cli.delete_branch("feature", true).unwrap();
+        assert!(cli.branches().iter().all(|b| b.name != "feature"));
+    }
+
+    #[test]
+    fn squash_merge_conflicts_are_resumed_with_the_edited_message() {
+        let tmp = TempDir::new("squash-conflict");
+        let cli = GitCli::new(tmp.0.clone(), Arc::default());
+        let commit = |message| cli.exec(&["commit", "-q", "-am", message]);
+
+        init_with_identity(&cli);
+        std::fs::write(tmp.0.join("file"), "base\n").unwrap();
+        cli.exec(&["add", "file"]);
+        commit("base");
+        cli.exec(&["switch", "-q", "-c", "other"]);
+        std::fs::write(tmp.0.join("file"), "other\n").unwrap();
+        commit("other");
+        cli.exec(&["switch", "-q", "-"]);
+        std::fs::write(tmp.0.join("file"), "main\n").unwrap();
+        commit("main");
+
+        assert!(matches!(
+            cli.merge("other", MergeMode::Squash, Some("Squash other")),
+            Err(GituiError::MergeConflict(_))
+        ));
+        assert_eq!(cli.operation(), Some(Operation::SquashMerge));
+
+        cli.checkout_side("file", Side::Theirs).unwrap();
+        cli.stage("file").unwrap();
+        let output = cli.run_operation(Operation::SquashMerge, "--continue");
+
+        assert!(output.success(), "{:?}", output);
+        assert_eq!(cli.operation(), None);
+        assert_eq!(
+            cli.stdout(&["log", "-1", "--format=%s"]).trim(),
+            "Squash other"
+        );
+    }
+
+    #[test]
+    fn discover_finds_the_root_from_a_subdirectory() {
+        let tmp = TempDir::new("discover");
+        let sub = tmp.0.join("a/b");
+        std::fs::create_dir_all(&sub).unwrap();
+
+        let log = Arc::<CommandLog>::default();
+
+        assert_eq!(discover(&tmp.0, log.clone()), None);
+
+        GitCli::new(tmp.0.clone(), Arc::default()).init().unwrap();
+
+        assert_eq!(
+            discover(&sub, log.clone()).map(|root| root.canonicalize().unwrap()),
+            Some(tmp.0.canonicalize().unwrap())
+        );
+        assert_eq!(
+            log.entries()
+                .iter()
+                .filter(|entry| entry.command() == "git rev-parse --show-toplevel")
+                .count(),
+            2
+        );
+    }
+
+    /// `git hang` runs a shell that runs `sleep`, neither of them git.
+    const HANG: [&str; 3] = ["-c", "alias.hang=!sleep 10", "hang"];
+
+    #[test]
+    fn exec_kills_the_whole_process_group_on_timeout() {
+        let mut cli = GitCli::new(std::env::temp_dir(), Arc::default());
+        cli.timeout = Some(Duration::from_millis(50));
+
+        let started = Instant::now();
+        let output = cli.exec(&HANG);
+
+        assert_eq!(
+            output.killed,
+            Some(Killed::TimedOut(Duration::from_millis(50)))
+        );
+        // sleep holding on to stdout would keep us waiting the full 10s
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn exec_kills_a_process_group_ignoring_sigterm_after_the_grace_period() {
+        let mut cli = GitCli::new(std::env::temp_dir(), Arc::default());
+        cli.timeout = Some(Duration::from_millis(50));
+
+        let started = Instant::now();
+        let output = cli.exec(&["-c", "alias.hang=!trap '' TERM; sleep 10", "hang"]);
+
+        assert!(output.killed.is_some());
+        assert!(started.elapsed() < GRACE + Duration::from_secs(3));
+    }
+
+    #[test]
+    fn exec_stops_when_cancelled() {
+        let cli = Arc::new(GitCli::new(std::env::temp_dir(), Arc::default()));
+
+        let canceller = cli.clone();
+        let handle = std::thread::spawn(move || {
+            while canceller.running.lock().unwrap().is_empty() {
+                std::thread::sleep(POLL);
+            }
+            canceller.cancel();
+        });
+
+        let output = cli.exec(&HANG);
+        handle.join().unwrap();
+
+        assert_eq!(output.killed, Some(Killed::Cancelled));
+        assert!(cli.running.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::VecDeque, path::PathBuf, sync::Mutex, time::Duration};
+
+use super::Killed;
+
+/// How many invocations `CommandLog` keeps, the oldest go first.
+const CAPACITY: usize = 500;
+
+/// One git invocation, browsed on `CurrentScreen::CommandLog`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub argv: Vec<String>,
+    pub cwd: PathBuf,
+    /// Exit code, `None` when git couldn't be started or was killed.
+    pub status: Option<i32>,
+    pub killed: Option<Killed>,
+    pub duration: Duration,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl LogEntry {
+    pub fn command(&self) -> String {
+        self.argv.join(" ")
+    }
+
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    /// How it ended, e.g. `exit 0` or `cancelled`.
+    pub fn outcome(&self) -> String {
+        match (self.killed, self.status) {
+            (Some(Killed::Cancelled), _) => "cancelled".to_string(),
+            (Some(Killed::TimedOut(after)), _) => format!("timed out after {}s", after.as_secs()),
+            (None, Some(code)) => format!("exit {}", code),
+            (None, None) => "no exit code".to_string(),
+        }
+    }
+}
+
+/// The last `CAPACITY` git invocations, shared by every thread running git.
+#[derive(Default)]
+pub struct CommandLog {
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl CommandLog {
+    pub fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Copy of the log, oldest first.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(n: usize) -> LogEntry {
+        LogEntry {
+            argv: vec!["git".to_string(), n.to_string()],
+            cwd: PathBuf::new(),
+            status: Some(0),
+            killed: None,
+            duration: Duration::ZERO,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_newest_entries() {
+        let log = CommandLog::default();
+
+        for n in 0..CAPACITY + 2 {
+            log.push(entry(n));
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), CAPACITY);
+        assert_eq!(entries[0], entry(2));
+        assert_eq!(entries[CAPACITY - 1], entry(CAPACITY + 1));
+    }
+}
@@ -0,0 +1,427 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use gix::{
+    refs::{Category, TargetRef},
+    remote::Direction,
+    state::InProgress,
+};
+
+use super::{CommandLog, GitBackend, GitCli, GitOutput};
+use crate::{
+    app::{
+        Branch, CommitSummary, Conflict, GituiError, Head, MergeMode, Operation, Side, Upstream,
+        Worktree,
+    },
+    workspace::RepoStatus,
+};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// `GitBackend` answering the reads done on every refresh (branches, HEAD
+/// and the operation in progress) with gitoxide, and handing everything
+/// else to `GitCli`.
+pub struct GixBackend {
+    /// Where the repository was found, re-opened for every read so config
+    /// changes made through `cli` (e.g. a new upstream) are picked up.
+    path: PathBuf,
+    cli: GitCli,
+}
+
+impl GixBackend {
+    pub fn open(dir: &Path, log: Arc<CommandLog>) -> Result<Self, Error> {
+        let repo = gix::discover(dir)?;
+        let path = repo.workdir().unwrap_or(repo.path()).to_path_buf();
+
+        Ok(Self {
+            path,
+            cli: GitCli::new(dir.to_path_buf(), log),
+        })
+    }
+
+    fn repo(&self) -> Result<gix::Repository, Error> {
+        Ok(gix::open(&self.path)?)
+    }
+
+    /// Same data as `parse_branches` gets out of `git for-each-ref`.
+    fn read_branches(&self) -> Result<Vec<Branch>, Error> {
+        let repo = self.repo()?;
+        let head = repo.head_name()?;
+        let refs = repo.references()?;
+        let mut branches = Vec::new();
+
+        for reference in refs.local_branches()?.chain(refs.remote_branches()?) {
+            let mut reference = reference?;
+
+            // symbolic refs like `origin/HEAD -> origin/main` aren't branches
+            if matches!(reference.target(), TargetRef::Symbolic(_)) {
+                continue;
+            }
+
+            let name = reference.name().shorten().to_string();
+            let is_remote = reference.name().category() == Some(Category::RemoteBranch);
+            let is_checked_out = head.as_ref().map(|h| h.as_ref()) == Some(reference.name());
+            let mut branch = if is_remote {
+                let remote = name.split_once('/').map(|(remote, _)| remote).unwrap_or("");
+                Branch::new_remote(&name, remote)
+            } else {
+                Branch::new(&name, is_checked_out)
+            };
+
+            let commit = reference.peel_to_commit()?;
+            let id = commit.id;
+
+            if branch.remote.is_none() {
+                if let Some(Ok(upstream)) =
+                    repo.branch_remote_tracking_ref_name(reference.name(), Direction::Fetch)
+                {
+                    let track = match repo.try_find_reference(upstream.as_ref())? {
+                        None => "gone".to_string(),
+                        Some(mut upstream) => {
+                            let upstream = upstream.peel_to_id()?.detach();
+                            let ahead = repo.rev_walk([id]).with_hidden([upstream]).all()?.count();
+                            let behind = repo.rev_walk([upstream]).with_hidden([id]).all()?.count();
+
+                            match (ahead, behind) {
+                                (0, 0) => String::new(),
+                                (ahead, 0) => format!("ahead {}", ahead),
+                                (0, behind) => format!("behind {}", behind),
+                                (ahead, behind) => format!("ahead {}, behind {}", ahead, behind),
+                            }
+                        }
+                    };
+                    branch.upstream = Some(Upstream::new(&upstream.shorten().to_string(), &track));
+                }
+            }
+
+            branch.tip = Some(CommitSummary {
+                id: commit.id().shorten_or_id().to_string(),
+                timestamp: commit.committer()?.time()?.seconds,
+                author: commit.author()?.name.to_string(),
+                subject: commit.message()?.summary().to_string(),
+            });
+
+            branches.push(branch);
+        }
+
+        Ok(branches)
+    }
+
+    fn read_head(&self) -> Result<Head, Error> {
+        let repo = self.repo()?;
+        let head = repo.head()?;
+
+        Ok(match head.referent_name() {
+            Some(name) => Head::Branch(name.shorten().to_string()),
+            None => match head.id() {
+                Some(id) => Head::Detached(id.shorten_or_id().to_string()),
+                None => Head::Unknown,
+            },
+        })
+    }
+}
+
+impl GitBackend for GixBackend {
+    fn branches(&self) -> Vec<Branch> {
+        self.read_branches().unwrap_or_else(|_| self.cli.branches())
+    }
+
+    fn head(&self) -> Head {
+        self.read_head().unwrap_or_else(|_| self.cli.head())
+    }
+
+    fn operation(&self) -> Option<Operation> {
+        let Ok(repo) = self.repo() else {
+            return self.cli.operation();
+        };
+
+        repo.state()
+            .map(|state| match state {
+                InProgress::ApplyMailbox
+                | InProgress::ApplyMailboxRebase
+                | InProgress::Rebase
+                | InProgress::RebaseInteractive => Operation::Rebase,
+                InProgress::Bisect => Operation::Bisect,
+                InProgress::CherryPick | InProgress::CherryPickSequence => Operation::CherryPick,
+                InProgress::Merge => Operation::Merge,
+                InProgress::Revert | InProgress::RevertSequence => Operation::Revert,
+            })
+            // gitoxide doesn't know about squash merges
+            .or_else(|| {
+                self.cli
+                    .squash_in_progress(repo.path())
+                    .then_some(Operation::SquashMerge)
+            })
+    }
+
+    fn run_operation(&self, operation: Operation, arg: &str) -> GitOutput {
+        self.cli.run_operation(operation, arg)
+    }
+
+    fn previous_branch(&self) -> Option<String> {
+        self.cli.previous_branch()
+    }
+
+    fn recent_checkouts(&self) -> Vec<String> {
+        self.cli.recent_checkouts()
+    }
+
+    fn protected_patterns(&self) -> Vec<String> {
+        self.cli.protected_patterns()
+    }
+
+    fn workspace_paths(&self) -> Vec<String> {
+        self.cli.workspace_paths()
+    }
+
+    fn status(&self) -> Option<RepoStatus> {
+        self.cli.status()
+    }
+
+    fn merged_branches(&self, base: &str) -> Result<Vec<String>, GituiError> {
+        self.cli.merged_branches(base)
+    }
+
+    fn commits(&self, range: &str) -> Result<Vec<CommitSummary>, GituiError> {
+        self.cli.commits(range)
+    }
+
+    fn diff_stat(&self, from: &str, to: &str) -> Result<String, GituiError> {
+        self.cli.diff_stat(from, to)
+    }
+
+    fn toplevel(&self) -> Option<String> {
+        self.cli.toplevel()
+    }
+
+    fn worktrees(&self) -> Vec<Worktree> {
+        self.cli.worktrees()
+    }
+
+    fn add_worktree(
+        &self,
+        path: &str,
+        branch: &str,
+        create: Option<&str>,
+    ) -> Result<(), GituiError> {
+        self.cli.add_worktree(path, branch, create)
+    }
+
+    fn remove_worktree(&self, path: &str) -> Result<(), GituiError> {
+        self.cli.remove_worktree(path)
+    }
+
+    fn prune_worktrees(&self) -> Result<(), GituiError> {
+        self.cli.prune_worktrees()
+    }
+
+    fn conflicts(&self) -> Vec<Conflict> {
+        self.cli.conflicts()
+    }
+
+    fn checkout_side(&self, path: &str, side: Side) -> Result<(), GituiError> {
+        self.cli.checkout_side(path, side)
+    }
+
+    fn stage(&self, path: &str) -> Result<(), GituiError> {
+        self.cli.stage(path)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), GituiError> {
+        self.cli.remove(path)
+    }
+
+    fn mergetool(&self, path: &str) -> Result<(), GituiError> {
+        self.cli.mergetool(path)
+    }
+
+    fn fetch_all(&self) -> Result<(), GituiError> {
+        self.cli.fetch_all()
+    }
+
+    fn switch(&self, branch: &str) -> Result<(), GituiError> {
+        self.cli.switch(branch)
+    }
+
+    fn switch_tracking(&self, remote_branch: &str, local: &str) -> Result<(), GituiError> {
+        self.cli.switch_tracking(remote_branch, local)
+    }
+
+    fn create_branch(
+        &self,
+        name: &str,
+        start_point: Option<&str>,
+        switch: bool,
+    ) -> Result<(), GituiError> {
+        self.cli.create_branch(name, start_point, switch)
+    }
+
+    fn merge(
+        &self,
+        branch: &str,
+        mode: MergeMode,
+        message: Option<&str>,
+    ) -> Result<(), GituiError> {
+        self.cli.merge(branch, mode, message)
+    }
+
+    fn commit(&self, message: Option<&str>) -> Result<(), GituiError> {
+        self.cli.commit(message)
+    }
+
+    fn abort_merge(&self) -> Result<(), GituiError> {
+        self.cli.abort_merge()
+    }
+
+    fn rebase(&self, onto: &str) -> Result<(), GituiError> {
+        self.cli.rebase(onto)
+    }
+
+    fn delete_branch(&self, name: &str, force: bool) -> Result<(), GituiError> {
+        self.cli.delete_branch(name, force)
+    }
+
+    fn rename_branch(&self, name: &str, new_name: &str) -> Result<(), GituiError> {
+        self.cli.rename_branch(name, new_name)
+    }
+
+    fn init(&self) -> Result<(), GituiError> {
+        self.cli.init()
+    }
+
+    fn cancel(&self) {
+        self.cli.cancel()
+    }
+
+    fn log(&self) -> Arc<CommandLog> {
+        self.cli.log()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=Ann", "-c", "user.email=ann@example.com"])
+            .args(["-c", "init.defaultBranch=main"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    fn sorted(mut branches: Vec<Branch>) -> Vec<Branch> {
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        branches
+    }
+
+    /// A clone with local branches ahead of, behind and diverged from their
+    /// upstreams, one whose upstream is gone, and a multi line subject.
+    fn clone_with_branches(tmp: &TempDir) -> PathBuf {
+        let upstream = tmp.0.join("upstream");
+        let clone = tmp.0.join("clone");
+        std::fs::create_dir_all(&upstream).unwrap();
+
+        git(&upstream, &["init", "-q"]);
+        git(
+            &upstream,
+            &["commit", "-q", "--allow-empty", "-m", "first\nline"],
+        );
+        git(&upstream, &["branch", "doomed"]);
+        git(&upstream, &["branch", "stale"]);
+        git(&tmp.0, &["clone", "-q", "upstream", "clone"]);
+        git(
+            &clone,
+            &["branch", "-q", "--track", "doomed", "origin/doomed"],
+        );
+        git(
+            &clone,
+            &["branch", "-q", "--track", "stale", "origin/stale"],
+        );
+        git(&clone, &["commit", "-q", "--allow-empty", "-m", "ahead"]);
+        git(
+            &upstream,
+            &["commit", "-q", "--allow-empty", "-m", "upstream"],
+        );
+        git(&upstream, &["branch", "-q", "-D", "doomed"]);
+        git(&upstream, &["switch", "-q", "stale"]);
+        git(
+            &upstream,
+            &["commit", "-q", "--allow-empty", "-m", "behind"],
+        );
+        git(&clone, &["fetch", "-q", "--prune"]);
+        git(&clone, &["switch", "-q", "-c", "local-only"]);
+
+        clone
+    }
+
+    #[test]
+    fn branches_match_the_git_executable() {
+        let tmp = TempDir::new("gix-branches");
+        let clone = clone_with_branches(&tmp);
+
+        let cli = GitCli::new(clone.clone(), Arc::default());
+        let gix = GixBackend::open(&clone, Arc::default()).unwrap();
+
+        let expected = sorted(cli.branches());
+        assert_eq!(expected.len(), 6);
+        assert_eq!(sorted(gix.read_branches().unwrap()), expected);
+    }
+
+    #[test]
+    fn head_matches_the_git_executable() {
+        let tmp = TempDir::new("gix-head");
+        let clone = clone_with_branches(&tmp);
+        let cli = GitCli::new(clone.clone(), Arc::default());
+        let gix = GixBackend::open(&clone, Arc::default()).unwrap();
+
+        assert_eq!(
+            gix.read_head().unwrap(),
+            Head::Branch("local-only".to_string())
+        );
+
+        git(&clone, &["switch", "-q", "--detach", "stale"]);
+
+        assert!(gix.read_head().unwrap().is_detached());
+        assert_eq!(gix.read_head().unwrap(), cli.head());
+    }
+
+    #[test]
+    fn operation_matches_the_git_executable() {
+        let tmp = TempDir::new("gix-operation");
+        let dir = tmp.0.clone();
+        git(&dir, &["init", "-q"]);
+        // `GitCli` doesn't pass the identity along, the merge below needs one
+        git(&dir, &["config", "user.name", "Ann"]);
+        git(&dir, &["config", "user.email", "ann@example.com"]);
+        std::fs::write(dir.join("file"), "base\n").unwrap();
+        git(&dir, &["add", "file"]);
+        git(&dir, &["commit", "-q", "-m", "base"]);
+        git(&dir, &["switch", "-q", "-c", "other"]);
+        std::fs::write(dir.join("file"), "other\n").unwrap();
+        git(&dir, &["commit", "-q", "-am", "other"]);
+        git(&dir, &["switch", "-q", "main"]);
+        std::fs::write(dir.join("file"), "main\n").unwrap();
+        git(&dir, &["commit", "-q", "-am", "main"]);
+
+        let cli = GitCli::new(dir.clone(), Arc::default());
+        let gix = GixBackend::open(&dir, Arc::default()).unwrap();
+        assert_eq!(gix.operation(), None);
+
+        assert!(matches!(
+            cli.merge("other", MergeMode::Default, None),
+            Err(GituiError::MergeConflict(_))
+        ));
+
+        assert_eq!(gix.operation(), Some(Operation::Merge));
+        assert_eq!(gix.operation(), cli.operation());
+    }
+}
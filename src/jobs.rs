@@ -0,0 +1,111 @@
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::app::GituiError;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+
+/// What a background job hands back to `App::update` once git is done.
+#[derive(Debug)]
+pub enum Message {
+    FetchedAll(Result<(), GituiError>),
+    Merged(Result<(), GituiError>),
+    Rebased(Result<(), GituiError>),
+}
+
+struct Job {
+    id: usize,
+    name: String,
+    started: Instant,
+}
+
+/// Runs slow git commands on worker threads so the UI keeps drawing.
+/// Only one job runs at a time, two git commands racing over the same
+/// repository rarely end well.
+pub struct Jobs {
+    running: Option<Job>,
+    next_id: usize,
+    sender: Sender<(usize, Message)>,
+    receiver: Receiver<(usize, Message)>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+
+        Self {
+            running: None,
+            next_id: 0,
+            sender,
+            receiver,
+        }
+    }
+
+    /// Starts `work` on a new thread, refusing with `GituiError::Busy` while
+    /// another job is still running.
+    pub fn spawn<F>(&mut self, name: &str, work: F) -> Result<(), GituiError>
+    where
+        F: FnOnce() -> Message + Send + 'static,
+    {
+        self.ensure_idle()?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            // the receiver only goes away with the app
+            let _ = sender.send((id, work()));
+        });
+
+        self.running = Some(Job {
+            id,
+            name: name.to_string(),
+            started: Instant::now(),
+        });
+
+        Ok(())
+    }
+
+    /// The result of the running job if it finished, never blocks.
+    pub fn finished(&mut self) -> Option<Message> {
+        let (id, message) = self.receiver.try_recv().ok()?;
+
+        if self.running.as_ref().map(|job| job.id) == Some(id) {
+            self.running = None;
+        }
+
+        Some(message)
+    }
+
+    /// Fails with `GituiError::Busy` while a job is running, for anything
+    /// else about to change the repository under it.
+    pub fn ensure_idle(&self) -> Result<(), GituiError> {
+        match &self.running {
+            Some(job) => Err(GituiError::Busy(job.name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// The running job's name behind a spinner frame picked by how long it
+    /// has been running, so redrawing on every tick animates it.
+    pub fn spinner(&self) -> Option<String> {
+        self.running.as_ref().map(|job| {
+            let frame = job.started.elapsed().as_millis() / SPINNER_FRAME.as_millis();
+            format!("{} {}", SPINNER[frame as usize % SPINNER.len()], job.name)
+        })
+    }
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,8 +1,10 @@
-use std::{error::Error, io};
+use std::{error::Error, io, path::PathBuf, time::Duration};
 
 use app::{BranchCommand, Index};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -12,13 +14,27 @@ use ratatui::{
 };
 
 mod app;
+mod git;
+mod jobs;
+#[cfg(test)]
+mod test_util;
 mod ui;
+mod workspace;
 use crate::{
-    app::{App, Branch, Branches, Command, CurrentScreen, Modal, Scrollable},
+    app::{
+        App, Command, Confirm, CurrentScreen, GituiError, MergeAction, MergeMode, Modal, NewBranch,
+        OperationAction, ProtectedAction, Scrollable, Side, Worktree,
+    },
+    git::GitBackend,
     ui::ui,
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let dir = repository_arg().unwrap_or_else(|err| {
+        eprintln!("gitui: {}", err);
+        std::process::exit(2);
+    });
+
     // setup terminal
     enable_raw_mode()?;
     let mut stderr = io::stderr(); // This is a special case. Normally using stdout is fine
@@ -27,7 +43,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
-    let mut app = App::new();
+    let mut app = App::open(dir);
     let res = run_app(&mut terminal, &mut app);
 
     // restore terminal
@@ -46,50 +62,121 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// The directory from `gitui [-C] [PATH]`, the current one by default.
+fn repository_arg() -> Result<PathBuf, String> {
+    let mut args = std::env::args().skip(1);
+
+    let dir = match args.next().as_deref() {
+        None => std::env::current_dir().map_err(|err| err.to_string())?,
+        Some("-C") => PathBuf::from(args.next().ok_or("-C needs a path")?),
+        Some(arg) if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+        Some(path) => PathBuf::from(path),
+    };
+
+    if let Some(arg) = args.next() {
+        return Err(format!("unexpected argument {}", arg));
+    }
+
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+
+    Ok(dir)
+}
+
+/// How long to wait for a key before redrawing, keeps the job spinner
+/// turning and picks up finished jobs.
+const TICK: Duration = Duration::from_millis(100);
+
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<bool> {
+    let mut refresh = true;
+
     loop {
+        refresh |= app.poll_jobs();
+
+        if refresh {
+            app.refresh_operation();
+            app.refresh_head();
+            refresh = false;
+        }
+
         terminal.draw(|f| ui(f, app))?;
 
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        refresh = true;
+
         if let Event::Key(key) = event::read()? {
             if key.kind == event::KeyEventKind::Release {
                 // Skip events that are not KeyEventKind::Press
                 continue;
             }
+
+            if key.code == KeyCode::Char('c')
+                && key.modifiers.contains(KeyModifiers::CONTROL)
+                && app.jobs.is_running()
+            {
+                app.cancel_job();
+                continue;
+            }
             match app.current_screen {
                 CurrentScreen::Main => match key.code {
                     KeyCode::Char('b') => {
                         app.current_screen = CurrentScreen::ListingBranches;
                         app.in_search_bar = true;
 
-                        let stdout = std::process::Command::new("git")
-                            .arg("branch")
-                            .output()
-                            .expect("to get git branches")
-                            .stdout;
-
-                        let branches: Vec<Branch> = String::from_utf8(stdout)
-                            .expect("couldnt parse stdout")
-                            .split("\n")
-                            .into_iter()
-                            .filter(|b| b.len() > 0)
-                            .map(|b| {
-                                let is_checked_out = b.contains("* ");
-                                let name = b.replace("* ", "");
-                                Branch::new(&name.trim_start(), is_checked_out)
-                            })
-                            .collect();
-
-                        app.branches = Branches::new(branches);
+                        app.branches.reload();
                     }
                     KeyCode::Char('c') => {
                         app.current_screen = CurrentScreen::ListingCommands;
                         app.in_search_bar = true;
                     }
+                    KeyCode::Char('o') => app.open_operation("".to_string()),
+                    KeyCode::Char('l') => app.open_command_log(),
+                    KeyCode::Char('w') => app.open_workspace(),
+                    KeyCode::Char('n') if app.head.is_detached() => {
+                        app.branches.reload();
+                        open_create_branch(app, None);
+                    }
+                    KeyCode::Char('-') => {
+                        app.branches.reload();
+                        app.branches
+                            .switch_previous()
+                            .unwrap_or_else(|err| app.show_error(err));
+                    }
                     KeyCode::Char('q') => {
                         app.current_screen = CurrentScreen::Exiting;
                     }
                     _ => {}
                 },
+                CurrentScreen::Workspace if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Enter => {
+                        if let Some(repo) = app.workspace.get_current() {
+                            let path = repo.path.clone();
+                            app.switch_repository(&path);
+                        }
+                    }
+                    KeyCode::Char('j') => {
+                        app.workspace.list.next();
+                    }
+                    KeyCode::Char('k') => {
+                        app.workspace.list.prev();
+                    }
+                    KeyCode::Char('r') => app.open_workspace(),
+                    KeyCode::Esc | KeyCode::Char('q') => app.close_workspace(),
+                    _ => {}
+                },
+                CurrentScreen::NoRepository => match key.code {
+                    KeyCode::Char('i') => app
+                        .init_repository()
+                        .unwrap_or_else(|err| app.show_error(err)),
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        return Ok(false);
+                    }
+                    _ => {}
+                },
                 CurrentScreen::Exiting => match key.code {
                     KeyCode::Char('y') => {
                         return Ok(true);
@@ -103,10 +190,8 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                     if app.in_search_bar && key.kind == KeyEventKind::Press =>
                 {
                     match key.code {
-                        KeyCode::Backspace => {
-                            if !app.search_query.is_empty() {
-                                app.search_query = remove_last_char(&app.search_query).to_string();
-                            }
+                        KeyCode::Backspace if !app.search_query.is_empty() => {
+                            app.search_query = remove_last_char(&app.search_query).to_string();
                         }
                         KeyCode::Esc => {
                             app.in_search_bar = false;
@@ -134,16 +219,7 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                         KeyCode::Enter => {
                             Command::from(app.commands.get_current().unwrap().0.as_str())
                                 .next_step(app)
-                                .unwrap_or_else(|err| {
-                                    if app.errors.len() > 0 {
-                                        app.errors.push(err);
-                                    } else {
-                                        app.errors = vec![err];
-                                    }
-
-                                    app.error_modal = Modal::Open;
-                                    app.current_screen = CurrentScreen::Errors;
-                                })
+                                .unwrap_or_else(|err| app.show_error(err))
                         }
                         KeyCode::Esc | KeyCode::Char('q') => {
                             app.current_screen = CurrentScreen::Main;
@@ -181,10 +257,8 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                     if app.in_search_bar && key.kind == KeyEventKind::Press =>
                 {
                     match key.code {
-                        KeyCode::Backspace => {
-                            if !app.search_query.is_empty() {
-                                app.search_query = remove_last_char(&app.search_query).to_string();
-                            }
+                        KeyCode::Backspace if !app.search_query.is_empty() => {
+                            app.search_query = remove_last_char(&app.search_query).to_string();
                         }
                         KeyCode::Esc => {
                             app.in_search_bar = false;
@@ -213,16 +287,7 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                             app.branch_commands.get_current().unwrap().0.as_str(),
                         )
                         .next_step(app)
-                        .unwrap_or_else(|err| {
-                            if app.errors.len() > 0 {
-                                app.errors.push(err);
-                            } else {
-                                app.errors = vec![err];
-                            }
-
-                            app.error_modal = Modal::Open;
-                            app.current_screen = CurrentScreen::Errors;
-                        }),
+                        .unwrap_or_else(|err| app.show_error(err)),
                         KeyCode::Esc | KeyCode::Char('q') => {
                             app.current_screen = CurrentScreen::Main;
                             app.branch_commands.reset_index();
@@ -261,45 +326,32 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                 {
                     match key.code {
                         KeyCode::Enter => match &app.selected_branch_command {
-                            Some(BranchCommand::Switch) => {
-                                app.branches.switch_current().unwrap_or_else(|err| {
-                                    if app.errors.len() > 0 {
-                                        app.errors.push(err);
-                                    } else {
-                                        app.errors = vec![err];
-                                    }
-
-                                    app.error_modal = Modal::Open;
-                                    app.current_screen = CurrentScreen::Errors;
-                                })
-                            }
-                            Some(BranchCommand::Merge) => {
-                                app.branches.merge_current().unwrap_or_else(|err| {
-                                    if app.errors.len() > 0 {
-                                        app.errors.push(err);
-                                    } else {
-                                        app.errors = vec![err];
-                                    }
-
-                                    app.error_modal = Modal::Open;
-                                    app.current_screen = CurrentScreen::Errors;
-                                })
+                            Some(BranchCommand::Switch) => switch_current_branch(app),
+                            Some(BranchCommand::Merge) => open_merge_modes(app),
+                            Some(BranchCommand::Create) => {
+                                let start_point = app.branches.get_current().map(|b| b.get_name());
+                                open_create_branch(app, start_point);
                             }
-
-                            None => app.branches.switch_current().unwrap_or_else(|err| {
-                                if app.errors.len() > 0 {
-                                    app.errors.push(err);
-                                } else {
-                                    app.errors = vec![err];
+                            Some(BranchCommand::Delete) => delete_current_branch(app),
+                            Some(BranchCommand::Compare) => app
+                                .open_comparison()
+                                .unwrap_or_else(|err| app.show_error(err)),
+                            Some(BranchCommand::CleanUp) => {
+                                if let Some(base) = app.branches.get_current().map(|b| b.get_name())
+                                {
+                                    app.open_cleanup(&base)
+                                        .unwrap_or_else(|err| app.show_error(err));
                                 }
+                            }
+                            Some(BranchCommand::Rename) => open_rename_branch(app),
+                            Some(BranchCommand::Rebase) => rebase_current_branch(app),
 
-                                app.error_modal = Modal::Open;
-                                app.current_screen = CurrentScreen::Errors;
-                            }),
+                            None => switch_current_branch(app),
                         },
                         KeyCode::Esc | KeyCode::Char('q') => {
                             app.current_screen = CurrentScreen::Main;
                             app.branches.reset_index();
+                            app.branches.clear_marks();
                         }
 
                         KeyCode::Char(value) => match value {
@@ -322,6 +374,29 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                             'i' => {
                                 app.in_search_bar = true;
                             }
+                            'n' => open_create_branch(app, None),
+                            'd' => delete_current_branch(app),
+                            'r' => open_rename_branch(app),
+                            's' => {
+                                let sort = app.branches.get_sort().next();
+                                app.branches.set_sort(sort);
+                            }
+                            '-' => app
+                                .ensure_idle()
+                                .and_then(|()| app.branches.switch_previous())
+                                .unwrap_or_else(|err| app.show_error(err)),
+                            'w' => {
+                                if let Some(branch) = app.branches.get_current() {
+                                    app.input = Worktree::default_path(app.git.as_ref(), branch);
+                                    app.current_screen = CurrentScreen::CreatingWorktree;
+                                }
+                            }
+                            'c' => app
+                                .open_comparison()
+                                .unwrap_or_else(|err| app.show_error(err)),
+                            ' ' => app.branches.toggle_mark_current(),
+                            'a' => app.branches.mark_all(),
+                            'v' => app.branches.invert_marks(),
                             c => {
                                 print!("{}", c)
                             }
@@ -335,10 +410,8 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                     if app.in_search_bar && key.kind == KeyEventKind::Press =>
                 {
                     match key.code {
-                        KeyCode::Backspace => {
-                            if !app.search_query.is_empty() {
-                                app.search_query = remove_last_char(&app.search_query).to_string();
-                            }
+                        KeyCode::Backspace if !app.search_query.is_empty() => {
+                            app.search_query = remove_last_char(&app.search_query).to_string();
                         }
                         KeyCode::Esc => {
                             app.in_search_bar = false;
@@ -360,6 +433,358 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                         _ => {}
                     }
                 }
+                CurrentScreen::CreatingBranch if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Enter => match app
+                            .ensure_idle()
+                            .and_then(|()| app.branches.create(&app.input, &app.new_branch))
+                        {
+                            Ok(()) => {
+                                app.current_screen = CurrentScreen::ListingBranches;
+                                app.input = "".to_string();
+                            }
+                            Err(err) => app.show_error(err),
+                        },
+                        KeyCode::Esc => {
+                            app.current_screen = CurrentScreen::ListingBranches;
+                            app.input = "".to_string();
+                        }
+                        KeyCode::Tab => {
+                            app.new_branch.start_point = match app.new_branch.start_point {
+                                Some(_) => None,
+                                None => app.branches.get_current().map(|b| b.get_name()),
+                            };
+                        }
+                        KeyCode::BackTab => {
+                            app.new_branch.switch = !app.new_branch.switch;
+                        }
+                        KeyCode::Backspace if !app.input.is_empty() => {
+                            app.input = remove_last_char(&app.input).to_string();
+                        }
+                        KeyCode::Char(value) => {
+                            app.input = format!("{}{}", app.input, value);
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::RenamingBranch if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Enter => match app
+                            .ensure_idle()
+                            .and_then(|()| app.branches.rename_current(&app.input))
+                        {
+                            Ok(()) => {
+                                app.current_screen = CurrentScreen::ListingBranches;
+                                app.input = "".to_string();
+                            }
+                            Err(err) => app.show_error(err),
+                        },
+                        KeyCode::Esc => {
+                            app.current_screen = CurrentScreen::ListingBranches;
+                            app.input = "".to_string();
+                        }
+                        KeyCode::Backspace if !app.input.is_empty() => {
+                            app.input = remove_last_char(&app.input).to_string();
+                        }
+                        KeyCode::Char(value) => {
+                            app.input = format!("{}{}", app.input, value);
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::Operation if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Enter => {
+                        if let Some((action, _)) = app.operation_actions.get_current() {
+                            OperationAction::from(action.as_str())
+                                .next_step(app)
+                                .unwrap_or_else(|err| app.show_error(err))
+                        }
+                    }
+                    KeyCode::Char('j') => {
+                        app.operation_actions.next();
+                    }
+                    KeyCode::Char('k') => {
+                        app.operation_actions.prev();
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::Conflicts if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('j') => {
+                        app.conflict_list.next();
+                    }
+                    KeyCode::Char('k') => {
+                        app.conflict_list.prev();
+                    }
+                    KeyCode::Char(' ') => app.conflict_list.toggle_mark_current(),
+                    KeyCode::Char('a') => app.conflict_list.mark_all(),
+                    KeyCode::Char('v') => app.conflict_list.invert_marks(),
+                    KeyCode::Char('o')
+                    | KeyCode::Char('t')
+                    | KeyCode::Char('r')
+                    | KeyCode::Char('m')
+                        if app.jobs.is_running() =>
+                    {
+                        app.ensure_idle().unwrap_or_else(|err| app.show_error(err))
+                    }
+                    KeyCode::Char('o') | KeyCode::Char('t') | KeyCode::Char('r') => {
+                        for conflict in app.get_selected_conflicts() {
+                            match key.code {
+                                KeyCode::Char('o') => conflict.take(app.git.as_ref(), Side::Ours),
+                                KeyCode::Char('t') => conflict.take(app.git.as_ref(), Side::Theirs),
+                                _ => conflict.mark_resolved(app.git.as_ref()),
+                            }
+                            .unwrap_or_else(|err| app.show_error(err));
+                        }
+                        if let CurrentScreen::Conflicts = app.current_screen {
+                            app.refresh_conflicts();
+                        }
+                    }
+                    KeyCode::Char('m') => {
+                        if let Some(conflict) = app.get_current_conflict() {
+                            let path = conflict.path.to_string();
+                            run_mergetool(terminal, app.git.as_ref(), &path)?
+                                .unwrap_or_else(|err| app.show_error(err));
+                        }
+                        if let CurrentScreen::Conflicts = app.current_screen {
+                            app.refresh_conflicts();
+                        }
+                    }
+                    KeyCode::Char('c') => MergeAction::Commit
+                        .run(app)
+                        .unwrap_or_else(|err| app.show_error(err)),
+                    KeyCode::Char('x') => MergeAction::Abort
+                        .run(app)
+                        .unwrap_or_else(|err| app.show_error(err)),
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.current_screen = CurrentScreen::Main;
+                        app.conflict_list.clear_marks();
+                    }
+                    _ => {}
+                },
+                CurrentScreen::CleaningUp if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Enter => app.run_cleanup(),
+                    KeyCode::Char(' ') => app.cleanup.toggle_mark_current(),
+                    KeyCode::Char('a') => app.cleanup.mark_all(),
+                    KeyCode::Char('v') => app.cleanup.invert_marks(),
+                    KeyCode::Char('j') => {
+                        if let Some((_, Index(i))) = Scrollable::from(&app.cleanup).next() {
+                            app.cleanup.select_from_index(*i);
+                        }
+                    }
+                    KeyCode::Char('k') => {
+                        if let Some((_, Index(i))) = Scrollable::from(&app.cleanup).prev() {
+                            app.cleanup.select_from_index(*i);
+                        }
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.current_screen = CurrentScreen::ListingBranches;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::Comparing if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('j') => {
+                        app.comparison_scroll = app.comparison_scroll.saturating_add(1);
+                    }
+                    KeyCode::Char('k') => {
+                        app.comparison_scroll = app.comparison_scroll.saturating_sub(1);
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.current_screen = CurrentScreen::ListingBranches;
+                        app.comparison = None;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::ListingWorktrees if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Char('j') => {
+                            app.worktree_list.next();
+                        }
+                        KeyCode::Char('k') => {
+                            app.worktree_list.prev();
+                        }
+                        KeyCode::Char('d') | KeyCode::Char('p') if app.jobs.is_running() => {
+                            app.ensure_idle().unwrap_or_else(|err| app.show_error(err))
+                        }
+                        KeyCode::Char('d') => {
+                            if let Some(worktree) = app.get_current_worktree() {
+                                worktree
+                                    .remove(app.git.as_ref())
+                                    .unwrap_or_else(|err| app.show_error(err));
+                            }
+                            app.refresh_worktrees();
+                        }
+                        KeyCode::Char('p') => {
+                            app.git
+                                .prune_worktrees()
+                                .unwrap_or_else(|err| app.show_error(err));
+                            app.refresh_worktrees();
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            app.current_screen = CurrentScreen::Main;
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::CommandLog if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Enter => {
+                        app.command_log_expanded = !app.command_log_expanded;
+                        app.command_log_scroll = 0;
+                    }
+                    KeyCode::Char('j') if app.command_log_expanded => {
+                        app.command_log_scroll = app.command_log_scroll.saturating_add(1);
+                    }
+                    KeyCode::Char('k') if app.command_log_expanded => {
+                        app.command_log_scroll = app.command_log_scroll.saturating_sub(1);
+                    }
+                    KeyCode::Char('j') => {
+                        app.command_log_list.next();
+                    }
+                    KeyCode::Char('k') => {
+                        app.command_log_list.prev();
+                    }
+                    KeyCode::Char('r') => app.open_command_log(),
+                    KeyCode::Esc | KeyCode::Char('q') if app.command_log_expanded => {
+                        app.command_log_expanded = false;
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::CreatingWorktree if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let result =
+                                app.ensure_idle()
+                                    .and_then(|()| match app.branches.get_current() {
+                                        Some(branch) => {
+                                            Worktree::add(app.git.as_ref(), &app.input, branch)
+                                        }
+                                        None => Ok(()),
+                                    });
+                            match result {
+                                Ok(()) => {
+                                    app.input = "".to_string();
+                                    app.current_screen = CurrentScreen::ListingWorktrees;
+                                    app.refresh_worktrees();
+                                }
+                                Err(err) => app.show_error(err),
+                            }
+                        }
+                        KeyCode::Esc => {
+                            app.current_screen = CurrentScreen::ListingBranches;
+                            app.input = "".to_string();
+                        }
+                        KeyCode::Backspace if !app.input.is_empty() => {
+                            app.input = remove_last_char(&app.input).to_string();
+                        }
+                        KeyCode::Char(value) => {
+                            app.input = format!("{}{}", app.input, value);
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::ChoosingMergeMode if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            app.merge_mode =
+                                MergeMode::from(app.merge_modes.get_current().unwrap().0.as_str());
+
+                            if app.merge_mode.has_message() {
+                                let into = app.branches.get_currently_checkedout_name();
+                                app.input = app
+                                    .branches
+                                    .get_current()
+                                    .map(|b| b.default_merge_message(into.as_deref()))
+                                    .unwrap_or_default();
+                                app.current_screen = CurrentScreen::EditingMergeMessage;
+                            } else {
+                                app.current_screen = CurrentScreen::ListingBranches;
+                                merge_current_branch(app, None);
+                            }
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            app.cancel_merge();
+                        }
+                        KeyCode::Char('j') => {
+                            app.merge_modes.next();
+                        }
+                        KeyCode::Char('k') => {
+                            app.merge_modes.prev();
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::EditingMergeMessage if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            app.current_screen = CurrentScreen::ListingBranches;
+                            let message = std::mem::take(&mut app.input);
+                            merge_current_branch(app, Some(&message));
+                        }
+                        KeyCode::Esc => {
+                            app.cancel_merge();
+                        }
+                        KeyCode::Backspace if !app.input.is_empty() => {
+                            app.input = remove_last_char(&app.input).to_string();
+                        }
+                        KeyCode::Char(value) => {
+                            app.input = format!("{}{}", app.input, value);
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::Confirming
+                    if key.kind == KeyEventKind::Press
+                        && app
+                            .confirm
+                            .as_ref()
+                            .is_some_and(|c| c.typed_answer().is_some()) =>
+                {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let answer = app.confirm.as_ref().and_then(|c| c.typed_answer());
+                            if answer == Some(app.input.as_str()) {
+                                app.input = "".to_string();
+                                app.current_screen = CurrentScreen::ListingBranches;
+                                if let Some(confirm) = app.confirm.take() {
+                                    run_confirmed(app, confirm);
+                                }
+                            }
+                        }
+                        KeyCode::Esc => {
+                            app.current_screen = CurrentScreen::ListingBranches;
+                            app.confirm = None;
+                            app.input = "".to_string();
+                        }
+                        KeyCode::Backspace if !app.input.is_empty() => {
+                            app.input = remove_last_char(&app.input).to_string();
+                        }
+                        KeyCode::Char(value) => {
+                            app.input = format!("{}{}", app.input, value);
+                        }
+                        _ => {}
+                    }
+                }
+                CurrentScreen::Confirming if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('y') => {
+                        app.current_screen = CurrentScreen::ListingBranches;
+                        if let Some(confirm) = app.confirm.take() {
+                            run_confirmed(app, confirm);
+                        }
+                    }
+                    KeyCode::Char('n') | KeyCode::Esc | KeyCode::Char('q') => {
+                        app.current_screen = CurrentScreen::ListingBranches;
+                        app.confirm = None;
+                        // drop any protected branch confirmation that led here
+                        app.branches.forget_protected();
+                        app.confirm_next_force_delete();
+                    }
+                    _ => {}
+                },
                 CurrentScreen::Errors if key.kind == KeyEventKind::Press => match key.code {
                     KeyCode::Esc | KeyCode::Char('q') => {
                         app.current_screen = CurrentScreen::Main;
@@ -374,8 +799,130 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
     }
 }
 
+fn open_create_branch(app: &mut App, start_point: Option<String>) {
+    app.current_screen = CurrentScreen::CreatingBranch;
+    app.input = "".to_string();
+    app.new_branch = NewBranch::new(start_point);
+}
+
+fn run_confirmed(app: &mut App, confirm: Confirm) {
+    match confirm {
+        Confirm::ForceDeleteBranch(name) => {
+            if let Err(err) = app
+                .ensure_idle()
+                .and_then(|()| app.branches.force_delete(&name))
+            {
+                show_branch_error(app, err);
+            }
+            app.confirm_next_force_delete();
+        }
+        Confirm::ProtectedBranch { branch, action } => {
+            app.branches.confirm_protected(&branch);
+            match action {
+                ProtectedAction::Delete => delete_current_branch(app),
+                ProtectedAction::Merge => open_merge_modes(app),
+                ProtectedAction::Rebase => rebase_current_branch(app),
+            }
+        }
+    }
+}
+
+/// Shows `err`, asking for a typed confirmation instead when it's about a
+/// protected branch.
+fn show_branch_error(app: &mut App, err: GituiError) {
+    match err {
+        GituiError::ProtectedBranch { branch, action } => {
+            app.input = "".to_string();
+            app.confirm = Some(Confirm::ProtectedBranch { branch, action });
+            app.current_screen = CurrentScreen::Confirming;
+        }
+        err => app.show_error(err),
+    }
+}
+
+fn open_merge_modes(app: &mut App) {
+    if app.branches.get_current().is_none() {
+        return;
+    }
+
+    match app
+        .branches
+        .peek_protected_checked_out(ProtectedAction::Merge)
+    {
+        Ok(()) => {
+            app.current_screen = CurrentScreen::ChoosingMergeMode;
+            app.merge_modes.reset_index();
+        }
+        Err(err) => show_branch_error(app, err),
+    }
+}
+
+fn switch_current_branch(app: &mut App) {
+    app.ensure_idle()
+        .and_then(|()| app.branches.switch_current())
+        .unwrap_or_else(|err| app.show_error(err));
+}
+
+fn rebase_current_branch(app: &mut App) {
+    if let Err(err) = app.rebase_current() {
+        show_branch_error(app, err);
+    }
+}
+
+fn merge_current_branch(app: &mut App, message: Option<&str>) {
+    if let Err(err) = app.merge_current(app.merge_mode, message) {
+        show_branch_error(app, err);
+    }
+}
+
+/// Runs `git mergetool` for `path` on the real terminal, restoring the
+/// TUI once the tool exits.
+fn run_mergetool<B: Backend>(
+    terminal: &mut Terminal<B>,
+    git: &dyn GitBackend,
+    path: &str,
+) -> io::Result<Result<(), GituiError>> {
+    disable_raw_mode()?;
+    execute!(io::stderr(), LeaveAlternateScreen, DisableMouseCapture)?;
+
+    let result = git.mergetool(path);
+
+    enable_raw_mode()?;
+    execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+
+    Ok(result)
+}
+
+fn open_rename_branch(app: &mut App) {
+    if let Some(branch) = app.branches.get_current() {
+        app.input = branch.get_name();
+        app.current_screen = CurrentScreen::RenamingBranch;
+    }
+}
+
+fn delete_current_branch(app: &mut App) {
+    if let Err(err) = app.ensure_idle() {
+        return app.show_error(err);
+    }
+
+    if !app.branches.get_marked().is_empty() {
+        app.delete_marked_branches();
+        return;
+    }
+
+    match app.branches.delete_current(false) {
+        Ok(()) => {}
+        Err(GituiError::BranchNotMerged(name)) => {
+            app.confirm = Some(Confirm::ForceDeleteBranch(name));
+            app.current_screen = CurrentScreen::Confirming;
+        }
+        Err(err) => show_branch_error(app, err),
+    }
+}
+
 fn remove_last_char(s: &str) -> &str {
-    if s.len() == 0 {
+    if s.is_empty() {
         return s;
     }
 
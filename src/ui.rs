@@ -7,7 +7,7 @@ use ratatui::{
     Frame,
 };
 
-use crate::app::{App, CurrentScreen, Modal};
+use crate::app::{validate_branch_name, App, CurrentScreen, Head, Index, Modal};
 
 pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
     // Create the layout sections.
@@ -24,11 +24,17 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
         .borders(Borders::ALL)
         .style(Style::default());
 
-    let title =
-        Paragraph::new(Text::styled("Gitui", Style::default().fg(Color::Green))).block(title_block);
+    let title = Paragraph::new(Line::from(vec![
+        Span::styled("Gitui", Style::default().fg(Color::Green)),
+        Span::styled(
+            format!("  {}", app.path.display()),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ]))
+    .block(title_block);
 
     f.render_widget(title, chunks[0]);
-    let current_navigation_text = vec![
+    let mut current_navigation_text = vec![
         // The first half of the text
         match app.current_screen {
             CurrentScreen::Main => Span::styled("Normal Mode", Style::default().fg(Color::Green)),
@@ -41,6 +47,47 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
             CurrentScreen::ListingBranches => {
                 Span::styled("Listing Branches", Style::default().fg(Color::Blue))
             }
+            CurrentScreen::CreatingBranch => {
+                Span::styled("Creating branch", Style::default().fg(Color::Green))
+            }
+            CurrentScreen::RenamingBranch => {
+                Span::styled("Renaming branch", Style::default().fg(Color::Green))
+            }
+            CurrentScreen::Confirming => {
+                Span::styled("Confirming", Style::default().fg(Color::LightRed))
+            }
+            CurrentScreen::Operation => Span::styled(
+                "Operation in progress",
+                Style::default().fg(Color::LightRed),
+            ),
+            CurrentScreen::Conflicts => {
+                Span::styled("Resolving conflicts", Style::default().fg(Color::LightRed))
+            }
+            CurrentScreen::ChoosingMergeMode => {
+                Span::styled("Choosing merge mode", Style::default().fg(Color::Green))
+            }
+            CurrentScreen::EditingMergeMessage => {
+                Span::styled("Editing merge message", Style::default().fg(Color::Green))
+            }
+            CurrentScreen::CleaningUp => {
+                Span::styled("Cleaning up branches", Style::default().fg(Color::LightRed))
+            }
+            CurrentScreen::Comparing => {
+                Span::styled("Comparing branches", Style::default().fg(Color::Blue))
+            }
+            CurrentScreen::ListingWorktrees => {
+                Span::styled("Listing worktrees", Style::default().fg(Color::Blue))
+            }
+            CurrentScreen::CreatingWorktree => {
+                Span::styled("Creating worktree", Style::default().fg(Color::Green))
+            }
+            CurrentScreen::CommandLog => {
+                Span::styled("Command log", Style::default().fg(Color::Blue))
+            }
+            CurrentScreen::NoRepository => {
+                Span::styled("No repository", Style::default().fg(Color::LightRed))
+            }
+            CurrentScreen::Workspace => Span::styled("Workspace", Style::default().fg(Color::Blue)),
             CurrentScreen::Exiting => Span::styled("Exiting", Style::default().fg(Color::LightRed)),
             CurrentScreen::Errors => Span::styled("Error", Style::default().fg(Color::Red)),
         }
@@ -52,7 +99,13 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
             match (&app.current_screen, &app.error_modal) {
                 (CurrentScreen::ListingBranches, Modal::Closed) => {
                     let msg = if let Some(name) = app.branches.get_currently_checkedout_name() {
-                        format!("Current branch: {}", name)
+                        format!(
+                            "Current branch: {} | sorted by {}",
+                            name,
+                            app.branches.get_sort()
+                        )
+                    } else if app.head.is_detached() {
+                        format!("{} | sorted by {}", app.head, app.branches.get_sort())
                     } else {
                         "No branch selected".to_string()
                     };
@@ -67,13 +120,45 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
         },
     ];
 
+    if app.head.is_detached() && !matches!(app.current_screen, CurrentScreen::ListingBranches) {
+        current_navigation_text.push(Span::styled(" | ", Style::default().fg(Color::White)));
+        current_navigation_text.push(Span::styled(
+            app.head.to_string(),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+
+    if let Some(operation) = app.operation {
+        current_navigation_text.push(Span::styled(" | ", Style::default().fg(Color::White)));
+        current_navigation_text.push(Span::styled(
+            format!("{} in progress", operation),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+
+    if let Some(spinner) = app.jobs.spinner() {
+        current_navigation_text.push(Span::styled(" | ", Style::default().fg(Color::White)));
+        current_navigation_text.push(Span::styled(
+            format!("{} (CTRL+c) to cancel", spinner),
+            Style::default().fg(Color::LightBlue),
+        ));
+    }
+
     let mode_footer = Paragraph::new(Line::from(current_navigation_text))
         .block(Block::default().borders(Borders::ALL));
 
     let current_keys_hint = {
         match app.current_screen {
+            CurrentScreen::Main if app.operation.is_some() => Span::styled(
+                "(q) to quit / (c) to list commands / (-) previous branch / (l) command log / (w) workspace / (o) to continue or abort",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::Main if app.head.is_detached() => Span::styled(
+                "(q) to quit / (c) to list commands / (-) previous branch / (l) command log / (w) workspace / (n) branch from detached HEAD",
+                Style::default().fg(Color::Red),
+            ),
             CurrentScreen::Main => Span::styled(
-                "(q) to quit / (c) to list commands",
+                "(q) to quit / (c) to list commands / (-) previous branch / (l) command log / (w) workspace",
                 Style::default().fg(Color::Red),
             ),
             CurrentScreen::ListingCommands => Span::styled(
@@ -85,9 +170,77 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
                 Style::default().fg(Color::Red),
             ),
             CurrentScreen::ListingBranches => Span::styled(
+                "(ESC|q) to cancel/(j/k) to navigate/(ENTER) to select/(n) new branch/(d) delete/(r) rename/(s) sort/(-) previous branch/(c) compare/(w) worktree/(SPACE|a|v) mark",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::CreatingBranch => Span::styled(
+                "(ESC) to cancel/(TAB) start point/(SHIFT+TAB) toggle switch/(ENTER) to create",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::RenamingBranch => Span::styled(
+                "(ESC) to cancel/(ENTER) to rename",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::Operation => Span::styled(
+                "(ESC|q) to go back/(j/k) to navigate/(ENTER) to select",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::Conflicts => Span::styled(
+                "(SPACE|a|v) mark/(o) ours/(t) theirs/(m) mergetool/(r) resolved/(c) commit/(x) abort",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::ChoosingMergeMode => Span::styled(
                 "(ESC|q) to cancel/(j/k) to navigate/(ENTER) to select",
                 Style::default().fg(Color::Red),
             ),
+            CurrentScreen::EditingMergeMessage => Span::styled(
+                "(ESC) to cancel/(ENTER) to merge",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::Comparing => Span::styled(
+                "(ESC|q) to go back/(j/k) to scroll the diffstat",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::CleaningUp => Span::styled(
+                "(ESC|q) to cancel/(j/k) to navigate/(SPACE|a|v) mark/(ENTER) to delete marked",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::ListingWorktrees => Span::styled(
+                "(ESC|q) to go back/(j/k) to navigate/(d) remove/(p) prune",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::CreatingWorktree => Span::styled(
+                "(ESC) to cancel/(ENTER) to add",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::CommandLog if app.command_log_expanded => Span::styled(
+                "(ESC|q) back to the log/(j/k) to scroll",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::CommandLog => Span::styled(
+                "(ESC|q) to go back/(j/k) to navigate/(ENTER) full output/(r) reload",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::NoRepository => Span::styled(
+                "(q) to quit/(i) to create a repository here",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::Workspace => Span::styled(
+                "(ESC|q) to go back/(j/k) to navigate/(ENTER) to open/(r) refresh",
+                Style::default().fg(Color::Red),
+            ),
+            CurrentScreen::Confirming
+                if app.confirm.as_ref().is_some_and(|c| c.typed_answer().is_some()) =>
+            {
+                Span::styled(
+                    "(ESC) to cancel/(ENTER) to confirm",
+                    Style::default().fg(Color::Red),
+                )
+            }
+            CurrentScreen::Confirming => Span::styled(
+                "(y) to confirm/(n|ESC) to cancel",
+                Style::default().fg(Color::Red),
+            ),
             CurrentScreen::Exiting => Span::styled(
                 "(q) to quit / (b) to list branches",
                 Style::default().fg(Color::Red),
@@ -121,7 +274,16 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
     f.render_widget(list_block, main_chunks[1]);
 
     match app.current_screen {
-        CurrentScreen::Errors | CurrentScreen::Main => (),
+        CurrentScreen::Errors
+        | CurrentScreen::Main
+        | CurrentScreen::Operation
+        | CurrentScreen::CleaningUp
+        | CurrentScreen::Comparing
+        | CurrentScreen::ListingWorktrees
+        | CurrentScreen::CommandLog
+        | CurrentScreen::NoRepository
+        | CurrentScreen::Workspace
+        | CurrentScreen::Conflicts => (),
         _ => {
             let search_block = if !app.in_search_bar {
                 Block::default()
@@ -148,28 +310,74 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
     }
 
     match app.current_screen {
-        CurrentScreen::ListingBranches => {
+        CurrentScreen::ListingBranches
+        | CurrentScreen::CreatingBranch
+        | CurrentScreen::RenamingBranch
+        | CurrentScreen::ChoosingMergeMode
+        | CurrentScreen::EditingMergeMessage
+        | CurrentScreen::CreatingWorktree
+        | CurrentScreen::Confirming => {
             let mut list_items = Vec::<ListItem>::new();
 
+            // pad names so the upstream column lines up
+            let name_width = app
+                .branches
+                .get_values()
+                .iter()
+                .map(|b| b.get_display_name().chars().count())
+                .max()
+                .unwrap_or(0);
+
             for (i, branch) in app.branches.get_values().iter().enumerate() {
                 let style = if app.branches.get_index() == i && !app.in_search_bar {
                     Style::default().fg(Color::Red).bg(Color::White)
+                } else if branch.is_remote() {
+                    Style::default().fg(Color::Cyan)
                 } else {
                     Style::default().fg(Color::Yellow)
                 };
-                let can_push = if !app.search_query.is_empty() {
-                    branch
-                        .get_display_name()
-                        .contains(app.search_query.as_str())
-                } else {
-                    true
-                };
+                if branch.matches(&app.search_query) {
+                    let mut spans = vec![
+                        mark_span(branch.is_marked),
+                        Span::styled(
+                            format!("{:width$}", branch.get_display_name(), width = name_width),
+                            style,
+                        ),
+                    ];
 
-                if can_push {
-                    list_items.push(ListItem::new(Line::from(Span::styled(
-                        branch.get_display_name(),
-                        style,
-                    ))));
+                    let (track, track_style) = match &branch.upstream {
+                        Some(upstream) if upstream.gone => (
+                            upstream.get_display_track(),
+                            Style::default().fg(Color::Red),
+                        ),
+                        Some(upstream) if upstream.ahead > 0 || upstream.behind > 0 => (
+                            upstream.get_display_track(),
+                            Style::default().fg(Color::Magenta),
+                        ),
+                        Some(upstream) => (
+                            upstream.get_display_track(),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                        None => ("".to_string(), Style::default()),
+                    };
+                    spans.push(Span::styled(format!("  {:<8}", track), track_style));
+
+                    if let Some(tip) = &branch.tip {
+                        spans.push(Span::styled(
+                            format!("{:<15}", tip.get_relative_date()),
+                            Style::default().fg(Color::Green),
+                        ));
+                        spans.push(Span::styled(
+                            format!("{}: ", tip.author),
+                            Style::default().fg(Color::Blue),
+                        ));
+                        spans.push(Span::styled(
+                            tip.subject.to_string(),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+
+                    list_items.push(ListItem::new(Line::from(spans)));
                 }
             }
 
@@ -243,6 +451,418 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
 
             f.render_widget(list, main_chunks[1].inner(&Margin::new(1, 1)));
         }
+        CurrentScreen::Operation => {
+            let operation_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(app.operation_actions.get_items().len() as u16 + 2),
+                    Constraint::Percentage(50),
+                    Constraint::Percentage(50),
+                ])
+                .split(chunks[1]);
+
+            let mut list_items = Vec::<ListItem>::new();
+
+            for (i, action) in app.operation_actions.get_items().iter().enumerate() {
+                let style = if app.operation_actions.get_index() == i {
+                    Style::default().fg(Color::Red).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+
+                list_items.push(ListItem::new(Line::from(Span::styled(
+                    action.0.to_string(),
+                    style,
+                ))));
+            }
+
+            let title = match app.operation {
+                Some(operation) => format!("{} in progress", operation),
+                None => "Nothing in progress".to_string(),
+            };
+            let list =
+                List::new(list_items).block(Block::default().title(title).borders(Borders::ALL));
+
+            f.render_widget(list, operation_chunks[0]);
+
+            let conflicts: Vec<ListItem> = app
+                .conflicts
+                .iter()
+                .map(|conflict| {
+                    ListItem::new(Line::from(Span::styled(
+                        conflict.get_display_name(),
+                        Style::default().fg(Color::Red),
+                    )))
+                })
+                .collect();
+
+            let conflicts_list = List::new(conflicts).block(
+                Block::default()
+                    .title("Conflicts (resolve and stage them, then continue)")
+                    .borders(Borders::ALL),
+            );
+
+            f.render_widget(conflicts_list, operation_chunks[1]);
+
+            let output = Paragraph::new(app.operation_output.to_string())
+                .block(Block::default().title("Output").borders(Borders::ALL))
+                .wrap(Wrap { trim: false });
+
+            f.render_widget(output, operation_chunks[2]);
+        }
+        CurrentScreen::Comparing => {
+            if let Some(comparison) = &app.comparison {
+                let compare_chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(chunks[1]);
+
+                let commit_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(compare_chunks[0]);
+
+                let sides = [
+                    (&comparison.only_left, &comparison.left, &comparison.right),
+                    (&comparison.only_right, &comparison.right, &comparison.left),
+                ];
+
+                for (i, (commits, from, not_in)) in sides.into_iter().enumerate() {
+                    let list_items: Vec<ListItem> = commits
+                        .iter()
+                        .map(|commit| {
+                            ListItem::new(Line::from(vec![
+                                Span::styled(
+                                    format!("{} ", commit.id),
+                                    Style::default().fg(Color::Yellow),
+                                ),
+                                Span::raw(commit.subject.to_string()),
+                                Span::styled(
+                                    format!(" ({})", commit.author),
+                                    Style::default().fg(Color::DarkGray),
+                                ),
+                            ]))
+                        })
+                        .collect();
+
+                    let list = List::new(list_items).block(
+                        Block::default()
+                            .title(format!(
+                                "{} commits in {} not in {}",
+                                commits.len(),
+                                from,
+                                not_in
+                            ))
+                            .borders(Borders::ALL),
+                    );
+
+                    f.render_widget(list, commit_chunks[i]);
+                }
+
+                let diffstat = Paragraph::new(comparison.diffstat.to_string())
+                    .block(
+                        Block::default()
+                            .title(format!(
+                                "Merging {} into {} brings in",
+                                comparison.left, comparison.right
+                            ))
+                            .borders(Borders::ALL),
+                    )
+                    .scroll((app.comparison_scroll, 0));
+
+                f.render_widget(diffstat, compare_chunks[1]);
+            }
+        }
+        CurrentScreen::CleaningUp => {
+            let mut list_items = Vec::<ListItem>::new();
+
+            for (i, branch) in app.cleanup.get_values().iter().enumerate() {
+                let style = if app.cleanup.get_index() == i {
+                    Style::default().fg(Color::Red).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+                let reason = if app.cleanup_merged.contains(&branch.name) {
+                    "merged"
+                } else {
+                    "upstream gone, unmerged"
+                };
+
+                list_items.push(ListItem::new(Line::from(vec![
+                    mark_span(branch.is_marked),
+                    Span::styled(branch.get_name(), style),
+                    Span::styled(
+                        format!("  {}", reason),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])));
+            }
+
+            if list_items.is_empty() {
+                list_items.push(ListItem::new(Line::from(Span::styled(
+                    "Nothing to clean up",
+                    Style::default().fg(Color::Green),
+                ))));
+            }
+
+            let list = List::new(list_items).block(
+                Block::default()
+                    .title("Branches to delete")
+                    .borders(Borders::ALL),
+            );
+
+            f.render_widget(list, chunks[1]);
+        }
+        CurrentScreen::ListingWorktrees => {
+            let mut list_items = Vec::<ListItem>::new();
+
+            let path_width = app
+                .worktrees
+                .iter()
+                .map(|w| w.path.chars().count())
+                .max()
+                .unwrap_or(0);
+
+            for (i, worktree) in app.worktrees.iter().enumerate() {
+                let style = if app.worktree_list.get_index() == i {
+                    Style::default().fg(Color::Red).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+                let prefix = if worktree.is_current { "* " } else { "  " };
+
+                let mut spans = vec![
+                    Span::styled(
+                        format!("{}{:<width$}", prefix, worktree.path, width = path_width),
+                        style,
+                    ),
+                    Span::styled(
+                        format!("  {}", worktree.get_display_branch()),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                ];
+                if worktree.is_locked {
+                    spans.push(Span::styled(
+                        "  locked",
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                if worktree.is_prunable {
+                    spans.push(Span::styled(
+                        "  prunable",
+                        Style::default().fg(Color::LightRed),
+                    ));
+                }
+
+                list_items.push(ListItem::new(Line::from(spans)));
+            }
+
+            let list = List::new(list_items)
+                .block(Block::default().title("Worktrees").borders(Borders::ALL));
+
+            f.render_widget(list, chunks[1]);
+        }
+        CurrentScreen::NoRepository => {
+            let text = vec![
+                Line::from(Span::styled(
+                    format!("{} is not in a git repository.", app.path.display()),
+                    Style::default().fg(Color::LightRed),
+                )),
+                Line::from(""),
+                Line::from("Start gitui inside a repository, or point it at one:"),
+                Line::from(Span::styled(
+                    "  gitui path/to/repo",
+                    Style::default().fg(Color::Yellow),
+                )),
+                Line::from(""),
+                Line::from("Or press (i) to run git init here."),
+            ];
+
+            let start = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL))
+                .wrap(Wrap { trim: false });
+
+            f.render_widget(start, chunks[1]);
+        }
+        CurrentScreen::Workspace => {
+            let mut list_items = Vec::<ListItem>::new();
+
+            let name_width = app
+                .workspace
+                .repos
+                .iter()
+                .map(|r| r.get_display_name().chars().count())
+                .max()
+                .unwrap_or(0);
+
+            for (i, repo) in app.workspace.repos.iter().enumerate() {
+                let style = if app.workspace.list.get_index() == i {
+                    Style::default().fg(Color::Red).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+                let prefix = if repo.path == app.path { "* " } else { "  " };
+
+                let mut spans = vec![Span::styled(
+                    format!(
+                        "{}{:<width$}",
+                        prefix,
+                        repo.get_display_name(),
+                        width = name_width
+                    ),
+                    style,
+                )];
+
+                match &repo.status {
+                    Some(status) => {
+                        spans.push(Span::styled(
+                            format!(
+                                "  {}",
+                                status.branch.as_deref().unwrap_or("(detached HEAD)")
+                            ),
+                            Style::default().fg(Color::Cyan),
+                        ));
+                        if status.dirty {
+                            spans.push(Span::styled(
+                                "  dirty",
+                                Style::default().fg(Color::LightRed),
+                            ));
+                        }
+                        if let Some(upstream) = &status.upstream {
+                            spans.push(Span::styled(
+                                format!("  {}", upstream.get_display_track()),
+                                Style::default().fg(Color::Magenta),
+                            ));
+                        }
+                    }
+                    None => spans.push(Span::styled(
+                        "  couldn't read status",
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                }
+
+                spans.push(Span::styled(
+                    format!("  {}", repo.path.display()),
+                    Style::default().fg(Color::DarkGray),
+                ));
+
+                list_items.push(ListItem::new(Line::from(spans)));
+            }
+
+            if list_items.is_empty() {
+                list_items.push(ListItem::new(Line::from(Span::styled(
+                    "No repositories. Add some with: git config --global --add gitui.workspace <path>",
+                    Style::default().fg(Color::DarkGray),
+                ))));
+            }
+
+            let list = List::new(list_items)
+                .block(Block::default().title("Workspace").borders(Borders::ALL));
+
+            f.render_widget(list, chunks[1]);
+        }
+        CurrentScreen::CommandLog if app.command_log_expanded => {
+            if let Some(entry) = app.get_current_log_entry() {
+                let heading = |text: &str| {
+                    Line::from(Span::styled(
+                        text.to_string(),
+                        Style::default().fg(Color::Cyan),
+                    ))
+                };
+
+                let mut lines = vec![
+                    Line::from(Span::styled(
+                        format!("$ {}", entry.command()),
+                        Style::default().fg(Color::Yellow),
+                    )),
+                    Line::from(format!("in {}", entry.cwd.display())),
+                    Line::from(format!(
+                        "{} after {}ms",
+                        entry.outcome(),
+                        entry.duration.as_millis()
+                    )),
+                    Line::from(""),
+                    heading("stdout"),
+                ];
+                lines.extend(entry.stdout.lines().map(|l| Line::from(l.to_string())));
+                lines.push(Line::from(""));
+                lines.push(heading("stderr"));
+                lines.extend(entry.stderr.lines().map(|l| Line::from(l.to_string())));
+
+                let output = Paragraph::new(lines)
+                    .block(Block::default().title("Output").borders(Borders::ALL))
+                    .scroll((app.command_log_scroll, 0));
+
+                f.render_widget(output, chunks[1]);
+            }
+        }
+        CurrentScreen::CommandLog => {
+            let mut list_items = Vec::<ListItem>::new();
+
+            for (i, entry) in app.command_log.iter().enumerate() {
+                let style = if app.command_log_list.get_index() == i {
+                    Style::default().fg(Color::Red).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+                let outcome_color = if entry.success() {
+                    Color::Green
+                } else {
+                    Color::LightRed
+                };
+
+                list_items.push(ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{:<14}", entry.outcome()),
+                        Style::default().fg(outcome_color),
+                    ),
+                    Span::styled(
+                        format!("{:>7}ms  ", entry.duration.as_millis()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(entry.command(), style),
+                ])));
+            }
+
+            let list = List::new(list_items).block(
+                Block::default()
+                    .title(format!(
+                        "{} git commands, newest first",
+                        app.command_log.len()
+                    ))
+                    .borders(Borders::ALL),
+            );
+
+            f.render_widget(list, chunks[1]);
+        }
+        CurrentScreen::Conflicts => {
+            let mut list_items = Vec::<ListItem>::new();
+
+            for (i, conflict) in app.conflicts.iter().enumerate() {
+                let style = if app.conflict_list.get_index() == i {
+                    Style::default().fg(Color::Red).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+
+                list_items.push(ListItem::new(Line::from(vec![
+                    mark_span(app.conflict_list.is_marked(&Index(i))),
+                    Span::styled(conflict.get_display_name(), style),
+                ])));
+            }
+
+            if list_items.is_empty() {
+                list_items.push(ListItem::new(Line::from(Span::styled(
+                    "All conflicts resolved. (c) to commit the merge",
+                    Style::default().fg(Color::Green),
+                ))));
+            }
+
+            let list = List::new(list_items)
+                .block(Block::default().title("Conflicts").borders(Borders::ALL));
+
+            f.render_widget(list, chunks[1]);
+        }
 
         _ => (),
     }
@@ -273,6 +893,122 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
         f.render_widget(list, area);
     }
 
+    match app.current_screen {
+        CurrentScreen::CreatingBranch => render_input_modal(
+            f,
+            "New branch",
+            "Name",
+            &app.input,
+            vec![
+                Line::from(format!(
+                    "Start point: {}",
+                    match (&app.new_branch.start_point, &app.head) {
+                        (Some(start_point), _) => start_point.to_string(),
+                        (None, head @ Head::Detached(_)) => head.to_string(),
+                        (None, _) => "HEAD".to_string(),
+                    }
+                )),
+                Line::from(format!(
+                    "Switch to it: {}",
+                    if app.new_branch.switch { "yes" } else { "no" }
+                )),
+                branch_name_validation(&app.input),
+            ],
+        ),
+        CurrentScreen::RenamingBranch => {
+            let title = match app.branches.get_current() {
+                Some(branch) => format!("Rename '{}'", branch.get_name()),
+                None => "Rename branch".to_string(),
+            };
+            render_input_modal(
+                f,
+                &title,
+                "Name",
+                &app.input,
+                vec![branch_name_validation(&app.input)],
+            )
+        }
+        CurrentScreen::CreatingWorktree => {
+            let title = match app.branches.get_current() {
+                Some(branch) => format!("New worktree for '{}'", branch.get_name()),
+                None => "New worktree".to_string(),
+            };
+            render_input_modal(f, &title, "Path", &app.input, vec![])
+        }
+        CurrentScreen::ChoosingMergeMode => {
+            let area = centered_rect(60, 25, f.size());
+            f.render_widget(Clear, area);
+
+            let list_items: Vec<ListItem> = app
+                .merge_modes
+                .get_items()
+                .iter()
+                .enumerate()
+                .map(|(i, mode)| {
+                    let style = if app.merge_modes.get_index() == i {
+                        Style::default().fg(Color::Red).bg(Color::White)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    };
+                    ListItem::new(Line::from(Span::styled(mode.0.to_string(), style)))
+                })
+                .collect();
+
+            let title = match app.branches.get_current() {
+                Some(branch) => format!("Merge '{}'", branch.get_name()),
+                None => "Merge".to_string(),
+            };
+            let list = List::new(list_items).block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .style(Style::default().bg(Color::DarkGray)),
+            );
+
+            f.render_widget(list, area);
+        }
+        CurrentScreen::EditingMergeMessage => render_input_modal(
+            f,
+            &format!("{} merge", app.merge_mode),
+            "Message",
+            &app.input,
+            vec![],
+        ),
+        _ => (),
+    }
+
+    if let (CurrentScreen::Confirming, Some(confirm)) = (&app.current_screen, &app.confirm) {
+        if confirm.typed_answer().is_some() {
+            render_input_modal(
+                f,
+                "Protected branch",
+                "Branch name",
+                &app.input,
+                vec![Line::from(Span::styled(
+                    confirm.prompt(),
+                    Style::default().fg(Color::Red),
+                ))],
+            );
+        } else {
+            let area = centered_rect(60, 25, f.size());
+            f.render_widget(Clear, area);
+
+            let popup_block = Block::default()
+                .title("Y/N")
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray));
+
+            let confirm_paragraph = Paragraph::new(Text::styled(
+                confirm.prompt(),
+                Style::default().fg(Color::Red),
+            ))
+            .block(popup_block)
+            .wrap(Wrap { trim: false });
+
+            f.render_widget(confirm_paragraph, area);
+        }
+    }
+
     if let CurrentScreen::Exiting = app.current_screen {
         f.render_widget(Clear, f.size()); //this clears the entire screen and anything already drawn
         let popup_block = Block::default()
@@ -294,6 +1030,55 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
     }
 }
 
+/// renders a popup with a single line text input followed by any extra lines
+fn render_input_modal<B: Backend>(
+    f: &mut Frame<B>,
+    title: &str,
+    label: &str,
+    input: &str,
+    extra: Vec<Line>,
+) {
+    let area = centered_rect(60, 25, f.size());
+    f.render_widget(Clear, area);
+
+    let popup_block = Block::default()
+        .title(title.to_string())
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+
+    let mut text = vec![Line::from(vec![
+        Span::raw(format!("{}: ", label)),
+        Span::styled(input.to_string(), Style::default().fg(Color::Yellow)),
+    ])];
+    text.extend(extra);
+
+    let paragraph = Paragraph::new(text)
+        .block(popup_block)
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(paragraph, area);
+}
+
+/// the marker shown in front of multi-selected list items
+fn mark_span(is_marked: bool) -> Span<'static> {
+    if is_marked {
+        Span::styled("+ ", Style::default().fg(Color::Green))
+    } else {
+        Span::raw("  ")
+    }
+}
+
+/// the reason `input` isn't a valid branch name, if any
+fn branch_name_validation(input: &str) -> Line<'static> {
+    match validate_branch_name(input) {
+        Err(err) if !input.is_empty() => Line::from(Span::styled(
+            err.to_string(),
+            Style::default().fg(Color::Red),
+        )),
+        _ => Line::from(""),
+    }
+}
+
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     // Cut the given rectangle into three vertical pieces
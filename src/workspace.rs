@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    app::{App, Index, Scrollable, Upstream},
+    git::{self, GitBackend},
+};
+
+/// The repositories configured with `gitui.workspace`, shown on
+/// `CurrentScreen::Workspace`. Each one gets its own `App`, parked here
+/// while another repository is open.
+pub struct Workspace {
+    pub repos: Vec<WorkspaceRepo>,
+    pub list: Scrollable,
+    parked: HashMap<PathBuf, App>,
+}
+
+pub struct WorkspaceRepo {
+    pub path: PathBuf,
+    /// `None` when git couldn't tell, e.g. the repository went away.
+    pub status: Option<RepoStatus>,
+}
+
+impl WorkspaceRepo {
+    pub fn get_display_name(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or(self.path.as_os_str())
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// What the workspace screen shows per repository, from
+/// `git status --porcelain=v2 --branch`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoStatus {
+    /// The checked out branch, `None` when HEAD is detached.
+    pub branch: Option<String>,
+    /// Uncommitted changes or untracked files.
+    pub dirty: bool,
+    pub upstream: Option<Upstream>,
+}
+
+impl Workspace {
+    /// Finds the repositories in `roots` and asks each for its status.
+    /// A root is either a repository or a directory with repositories in it.
+    /// `git` is the open repository at `path`, the others ask through their
+    /// parked app if they have one, recording into `git`'s log otherwise.
+    pub fn load(&mut self, roots: &[PathBuf], path: &Path, git: &Arc<dyn GitBackend>) {
+        self.repos = find_repos(roots)
+            .into_iter()
+            .map(|repo| {
+                let status = match self.parked.get(&repo) {
+                    _ if repo == path => git.status(),
+                    Some(app) => app.git.status(),
+                    None => git::open(repo.clone(), git.log()).status(),
+                };
+
+                WorkspaceRepo { status, path: repo }
+            })
+            .collect();
+
+        self.list = Scrollable::new(
+            self.repos
+                .iter()
+                .enumerate()
+                .map(|(i, repo)| (repo.get_display_name(), Index(i)))
+                .collect(),
+            Some(
+                self.list
+                    .get_index()
+                    .min(self.repos.len().saturating_sub(1)),
+            ),
+        );
+    }
+
+    pub fn get_current(&self) -> Option<&WorkspaceRepo> {
+        self.list
+            .get_current()
+            .and_then(|(_, Index(i))| self.repos.get(*i))
+    }
+
+    pub fn park(&mut self, app: App) {
+        self.parked.insert(app.path.clone(), app);
+    }
+
+    /// The state left behind in the repository at `path`, if it was open before.
+    pub fn unpark(&mut self, path: &Path) -> Option<App> {
+        self.parked.remove(path)
+    }
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self {
+            repos: Vec::new(),
+            list: Scrollable::new(vec![], Some(0)),
+            parked: HashMap::new(),
+        }
+    }
+}
+
+fn is_repo(dir: &Path) -> bool {
+    // a file for worktrees and submodules
+    dir.join(".git").exists()
+}
+
+/// `roots` that are repositories and the repositories directly inside the
+/// others, canonicalized so they compare equal to `App::path`.
+fn find_repos(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+
+    for root in roots {
+        let found = if is_repo(root) {
+            vec![root.to_path_buf()]
+        } else {
+            let mut children: Vec<PathBuf> = std::fs::read_dir(root)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir() && is_repo(path))
+                .collect();
+            children.sort();
+            children
+        };
+
+        for repo in found {
+            let repo = repo.canonicalize().unwrap_or(repo);
+            if !repos.contains(&repo) {
+                repos.push(repo);
+            }
+        }
+    }
+
+    repos
+}
+
+/// Parses `git status --porcelain=v2 --branch`.
+pub fn parse_status(output: &str) -> RepoStatus {
+    let mut status = RepoStatus {
+        branch: None,
+        dirty: false,
+        upstream: None,
+    };
+
+    for line in output.lines() {
+        match line.strip_prefix("# ") {
+            Some(header) => match header.split_once(' ') {
+                Some(("branch.head", "(detached)")) => {}
+                Some(("branch.head", branch)) => status.branch = Some(branch.to_string()),
+                // without a `branch.ab` line the upstream is gone
+                Some(("branch.upstream", name)) => {
+                    status.upstream = Some(Upstream::new(name, "gone"))
+                }
+                Some(("branch.ab", ab)) => {
+                    if let Some(upstream) = &mut status.upstream {
+                        let mut counts = ab
+                            .split(' ')
+                            .map(|n| n.trim_start_matches(['+', '-']).parse().unwrap_or(0));
+                        upstream.gone = false;
+                        upstream.ahead = counts.next().unwrap_or(0);
+                        upstream.behind = counts.next().unwrap_or(0);
+                    }
+                }
+                _ => {}
+            },
+            None => status.dirty |= !line.is_empty(),
+        }
+    }
+
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        git::{
+            fake::{FakeGit, FakeRepo},
+            GitCli,
+        },
+        test_util::TempDir,
+    };
+
+    #[test]
+    fn parses_branch_dirty_state_and_ahead_behind() {
+        let status = parse_status(
+            "# branch.oid 1234\n\
+             # branch.head main\n\
+             # branch.upstream origin/main\n\
+             # branch.ab +1 -2\n\
+             ? notes.txt\n",
+        );
+
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert!(status.dirty);
+        let upstream = status.upstream.unwrap();
+        assert_eq!(upstream.name, "origin/main");
+        assert_eq!(
+            (upstream.ahead, upstream.behind, upstream.gone),
+            (1, 2, false)
+        );
+    }
+
+    #[test]
+    fn parses_a_clean_detached_head_and_a_gone_upstream() {
+        let detached = parse_status("# branch.oid 1234\n# branch.head (detached)\n");
+        assert_eq!(detached.branch, None);
+        assert!(!detached.dirty);
+
+        let gone = parse_status("# branch.head main\n# branch.upstream origin/main\n");
+        assert!(gone.upstream.unwrap().gone);
+    }
+
+    #[test]
+    fn finds_roots_that_are_repos_and_repos_inside_the_others() {
+        let tmp = TempDir::new("find-repos");
+        for dir in [
+            "code/b/.git",
+            "code/a/.git",
+            "code/not-a-repo",
+            "single/.git",
+        ] {
+            std::fs::create_dir_all(tmp.0.join(dir)).unwrap();
+        }
+        let root = tmp.0.canonicalize().unwrap();
+
+        let repos = find_repos(&[
+            tmp.0.join("single"),
+            tmp.0.join("code"),
+            tmp.0.join("single"),
+        ]);
+
+        assert_eq!(
+            repos,
+            vec![
+                root.join("single"),
+                root.join("code/a"),
+                root.join("code/b")
+            ]
+        );
+    }
+
+    #[test]
+    fn load_asks_the_open_and_parked_repositories_through_their_backends() {
+        let tmp = TempDir::new("load-parked");
+        for name in ["api", "cli", "web"] {
+            let dir = tmp.0.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            GitCli::new(dir, Arc::default()).init().unwrap();
+        }
+        let root = tmp.0.canonicalize().unwrap();
+
+        let mut workspace = Workspace::default();
+        // a fake says nothing is checked out, git would say the unborn branch
+        let parked: Arc<dyn GitBackend> = Arc::new(FakeGit::new(FakeRepo::default()));
+        workspace.park(App::new(parked, root.join("api")));
+        let open: Arc<dyn GitBackend> = Arc::new(FakeGit::new(FakeRepo::default()));
+        workspace.load(std::slice::from_ref(&root), &root.join("web"), &open);
+
+        let checked_out: Vec<_> = workspace
+            .repos
+            .iter()
+            .map(|repo| repo.status.as_ref().unwrap().branch.is_some())
+            .collect();
+        assert_eq!(checked_out, vec![false, true, false]);
+    }
+}